@@ -107,7 +107,7 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
 
             fn my_pos_reaction(&self, vec: &mut #reaction_vec) {
                 if self.jump_index().is_some() {
-                    vec.push(#reaction { name: "*" });
+                    vec.push(#reaction { name: "🎯" });
                 }
             }
         }
@@ -196,7 +196,10 @@ async fn dm_help(ctx: Arc<Context>, msg: &Message) -> BotResult<()> {
         "description size {size} > {DESCRIPTION_SIZE}",
     );
 
-    let mut cmds: Vec<_> = PREFIX_COMMANDS.iter().collect();
+    let mut cmds: Vec<_> = PREFIX_COMMANDS
+        .iter()
+        .filter(|cmd| !cmd.flags.only_owner())
+        .collect();
 
     cmds.sort_unstable_by(|a, b| a.group.cmp(&b.group).then_with(|| a.name().cmp(b.name())));
     cmds.dedup_by_key(|cmd| cmd.name());
@@ -0,0 +1,337 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use rosu_v2::prelude::{GameMode, Grade, Score};
+use time::{macros::format_description, Date, Month};
+
+use crate::commands::GradeOption;
+
+/// Whitespace-separated list of conditions that a score must all satisfy,
+/// e.g. `acc>99 miss=0 grade>=S date>2023-01-01 lazer`.
+#[derive(Debug, PartialEq)]
+pub struct ScoreFilters(Vec<ScoreFilter>);
+
+#[derive(Debug, PartialEq)]
+enum ScoreFilter {
+    Acc(Op, f32),
+    Combo(Op, u32),
+    Date(Op, Date),
+    FullCombo(bool),
+    Grade(Op, u8),
+    Lazer(bool),
+    Misses(Op, u32),
+    Name(NameOp, String),
+    Pp(Op, f32),
+    Score(Op, u32),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Op {
+    Less,
+    LessEq,
+    Eq,
+    NotEq,
+    GreaterEq,
+    Greater,
+}
+
+impl Op {
+    fn apply<T: PartialOrd>(self, lhs: T, rhs: T) -> bool {
+        let Some(ordering) = lhs.partial_cmp(&rhs) else {
+            return false;
+        };
+
+        match self {
+            Self::Less => ordering == Ordering::Less,
+            Self::LessEq => ordering != Ordering::Greater,
+            Self::Eq => ordering == Ordering::Equal,
+            Self::NotEq => ordering != Ordering::Equal,
+            Self::GreaterEq => ordering != Ordering::Less,
+            Self::Greater => ordering == Ordering::Greater,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum NameOp {
+    Eq,
+    NotEq,
+    Contains,
+}
+
+const KEYWORDS: [&str; 4] = ["fc", "lazer", "stable", "classic"];
+const OPERATORS: [char; 5] = ['<', '>', '=', '!', '~'];
+
+impl ScoreFilters {
+    /// Whether the argument looks like it's meant to be a filter condition
+    pub fn is_term(arg: &str) -> bool {
+        let keyword = arg.strip_prefix('!').unwrap_or(arg);
+
+        KEYWORDS.iter().any(|kw| keyword.eq_ignore_ascii_case(kw))
+            || arg.find(OPERATORS).map_or(false, |idx| idx > 0)
+    }
+
+    /// Whether a condition depends on the score's pp
+    pub fn needs_pp(&self) -> bool {
+        self.0
+            .iter()
+            .any(|filter| matches!(filter, ScoreFilter::Pp(..)))
+    }
+
+    /// Check whether the score satisfies all conditions.
+    ///
+    /// `max_combo` is the map's max combo, if available, and
+    /// `pp` the pp that the score is displayed with.
+    pub fn matches(
+        &self,
+        score: &Score,
+        mode: GameMode,
+        max_combo: Option<u32>,
+        pp: Option<f32>,
+    ) -> bool {
+        self.0
+            .iter()
+            .all(|filter| filter.matches(score, mode, max_combo, pp))
+    }
+}
+
+impl ScoreFilter {
+    fn matches(
+        &self,
+        score: &Score,
+        mode: GameMode,
+        max_combo: Option<u32>,
+        pp: Option<f32>,
+    ) -> bool {
+        match self {
+            Self::Acc(op, acc) => op.apply(score.accuracy, *acc),
+            Self::Combo(op, combo) => op.apply(score.max_combo, *combo),
+            Self::Date(op, date) => op.apply(score.ended_at.date(), *date),
+            Self::FullCombo(fc) => is_fc(score, mode, max_combo) == *fc,
+            Self::Grade(op, grade) => op.apply(grade_rank(score.grade), *grade),
+            Self::Lazer(lazer) => score.legacy_score_id.is_none() == *lazer,
+            Self::Misses(op, misses) => op.apply(score.statistics.miss, *misses),
+            Self::Name(op, name) => {
+                let username = score
+                    .user
+                    .as_ref()
+                    .map(|user| normalize_name(&user.username))
+                    .unwrap_or_default();
+
+                match op {
+                    NameOp::Eq => username == *name,
+                    NameOp::NotEq => username != *name,
+                    NameOp::Contains => username.contains(name.as_str()),
+                }
+            }
+            Self::Pp(op, value) => pp.map_or(false, |pp| op.apply(pp, *value)),
+            Self::Score(op, value) => op.apply(score.score, *value),
+        }
+    }
+}
+
+impl FromStr for ScoreFilters {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_whitespace()
+            .map(ScoreFilter::from_str)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl FromStr for ScoreFilter {
+    type Err = String;
+
+    fn from_str(term: &str) -> Result<Self, Self::Err> {
+        let lowercase = term.to_ascii_lowercase();
+
+        let (negated, keyword) = match lowercase.strip_prefix('!') {
+            Some(keyword) => (true, keyword),
+            None => (false, lowercase.as_str()),
+        };
+
+        match keyword {
+            "fc" => return Ok(Self::FullCombo(!negated)),
+            "lazer" => return Ok(Self::Lazer(!negated)),
+            "stable" | "classic" => return Ok(Self::Lazer(negated)),
+            _ => {}
+        }
+
+        let Some(idx) = lowercase.find(OPERATORS).filter(|&idx| idx > 0) else {
+            return Err(format!(
+                "Failed to parse filter `{term}`.\n\
+                Conditions must be of the form `key<operator>value` like `acc>99`, \
+                or one of the keywords `fc`, `lazer`, or `stable`."
+            ));
+        };
+
+        let key = &lowercase[..idx];
+        let rest = &lowercase[idx..];
+
+        let (op, value) = ["<=", ">=", "!=", "==", "<", ">", "=", "~"]
+            .into_iter()
+            .find_map(|op| rest.strip_prefix(op).map(|value| (op, value)))
+            .ok_or_else(|| format!("Failed to parse the operator of filter `{term}`"))?;
+
+        if value.is_empty() {
+            return Err(format!("Filter `{term}` is missing a value"));
+        }
+
+        let filter = match key {
+            "name" | "player" | "user" => {
+                let op = match op {
+                    "=" | "==" => NameOp::Eq,
+                    "!=" => NameOp::NotEq,
+                    "~" => NameOp::Contains,
+                    _ => {
+                        return Err(format!(
+                            "Filter `{term}` can only use the operators `=`, `!=`, or `~`"
+                        ))
+                    }
+                };
+
+                Self::Name(op, normalize_name(value))
+            }
+            "acc" | "accuracy" => Self::Acc(parse_op(op, term)?, parse_value(value, term)?),
+            "combo" => Self::Combo(parse_op(op, term)?, parse_value(value, term)?),
+            "miss" | "misses" => Self::Misses(parse_op(op, term)?, parse_value(value, term)?),
+            "pp" => Self::Pp(parse_op(op, term)?, parse_value(value, term)?),
+            "score" => Self::Score(parse_op(op, term)?, parse_value(value, term)?),
+            "grade" | "rank" => {
+                let grade = Grade::from(value.parse::<GradeOption>()?);
+
+                Self::Grade(parse_op(op, term)?, grade_rank(grade))
+            }
+            "date" | "time" => Self::Date(parse_op(op, term)?, parse_date(value, term)?),
+            _ => {
+                return Err(format!(
+                    "Unknown filter key `{key}`.\n\
+                    Valid keys are: `acc`, `combo`, `date`, `grade`, `miss`, `name`, `pp`, or `score`"
+                ))
+            }
+        };
+
+        Ok(filter)
+    }
+}
+
+fn parse_op(op: &str, term: &str) -> Result<Op, String> {
+    let op = match op {
+        "<" => Op::Less,
+        "<=" => Op::LessEq,
+        "=" | "==" => Op::Eq,
+        "!=" => Op::NotEq,
+        ">=" => Op::GreaterEq,
+        ">" => Op::Greater,
+        _ => {
+            return Err(format!(
+                "Operator `{op}` is not supported for filter `{term}`"
+            ))
+        }
+    };
+
+    Ok(op)
+}
+
+fn parse_value<T: FromStr>(value: &str, term: &str) -> Result<T, String> {
+    value
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("Failed to parse the number `{value}` of filter `{term}`"))
+}
+
+/// Accepts either `YYYY-MM-DD` or just `YYYY`
+fn parse_date(value: &str, term: &str) -> Result<Date, String> {
+    let date = match value.parse::<i32>() {
+        Ok(year) => Date::from_calendar_date(year, Month::January, 1).ok(),
+        Err(_) => Date::parse(value, format_description!("[year]-[month]-[day]")).ok(),
+    };
+
+    date.ok_or_else(|| {
+        format!("Failed to parse the date `{value}` of filter `{term}`, expected `YYYY-MM-DD`")
+    })
+}
+
+/// Usernames are compared case-insensitively and
+/// underscores are treated as whitespace
+fn normalize_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('_', " ")
+}
+
+fn grade_rank(grade: Grade) -> u8 {
+    match grade {
+        Grade::F => 0,
+        Grade::D => 1,
+        Grade::C => 2,
+        Grade::B => 3,
+        Grade::A => 4,
+        Grade::S | Grade::SH => 5,
+        Grade::X | Grade::XH => 6,
+    }
+}
+
+fn is_fc(score: &Score, mode: GameMode, max_combo: Option<u32>) -> bool {
+    if score.statistics.miss > 0 {
+        return false;
+    }
+
+    match (mode, max_combo) {
+        // Mania combo is not affected by slider ends and the like
+        (GameMode::Mania, _) | (_, None) => true,
+        // Allow a few dropped slider ends
+        (_, Some(max_combo)) => score.max_combo + max_combo / 100 >= max_combo,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_filters() {
+        let filters: ScoreFilters = "acc>99 miss=0 grade>=S date>2023-01-01 lazer name~peppy"
+            .parse()
+            .unwrap();
+
+        let expected = ScoreFilters(vec![
+            ScoreFilter::Acc(Op::Greater, 99.0),
+            ScoreFilter::Misses(Op::Eq, 0),
+            ScoreFilter::Grade(Op::GreaterEq, 5),
+            ScoreFilter::Date(
+                Op::Greater,
+                Date::from_calendar_date(2023, Month::January, 1).unwrap(),
+            ),
+            ScoreFilter::Lazer(true),
+            ScoreFilter::Name(NameOp::Contains, "peppy".to_owned()),
+        ]);
+
+        assert_eq!(filters, expected);
+    }
+
+    #[test]
+    fn parse_name_aliases() {
+        let filters: ScoreFilters = "player=peppy user!=Cookiezi".parse().unwrap();
+
+        let expected = ScoreFilters(vec![
+            ScoreFilter::Name(NameOp::Eq, "peppy".to_owned()),
+            ScoreFilter::Name(NameOp::NotEq, "cookiezi".to_owned()),
+        ]);
+
+        assert_eq!(filters, expected);
+    }
+
+    #[test]
+    fn only_pp_conditions_need_pp() {
+        assert!("acc>99 pp>=500".parse::<ScoreFilters>().unwrap().needs_pp());
+        assert!(!"acc>99 fc".parse::<ScoreFilters>().unwrap().needs_pp());
+    }
+
+    #[test]
+    fn parse_filter_errors() {
+        assert!("acc>abc".parse::<ScoreFilters>().is_err());
+        assert!("bpm>200".parse::<ScoreFilters>().is_err());
+        assert!("combo~5".parse::<ScoreFilters>().is_err());
+        assert!("date<2023-13-01".parse::<ScoreFilters>().is_err());
+    }
+}
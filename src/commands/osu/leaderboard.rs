@@ -1,39 +1,55 @@
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, fmt::Write, sync::Arc, time::Duration};
 
 use command_macros::{command, HasMods, SlashCommand};
 use eyre::Report;
-use rosu_v2::error::OsuError;
+use rosu_v2::prelude::{BeatmapExtended, BeatmapsetExtended, Score, UserId as OsuUserId};
 use twilight_interactions::command::{CommandModel, CreateCommand};
-use twilight_model::channel::{message::MessageType, Message};
+use twilight_model::{
+    channel::{message::MessageType, Message},
+    id::{marker::GuildMarker, Id},
+};
 
 use crate::{
     core::{
         commands::{prefix::Args, CommandOrigin},
         InteractionCommand,
     },
-    embeds::{EmbedData, LeaderboardEmbed},
-    pagination::{LeaderboardPagination, Pagination},
-    pp::PpCalculator,
+    custom_client::{CustomClientError, ExtendedLeaderboard, LeaderboardScope, Requester},
+    embeds::{
+        EmbedData, LeaderboardEmbed, LeaderboardStatsEmbed, LeaderboardUser,
+        MapsetLeaderboardEmbed, MapsetLeaderboardEntry, MAPSET_DIFF_LIMIT,
+    },
+    pagination::{LeaderboardPagination, Pagination, PaginationStyle},
+    pp::{score_pp, PpCalculator},
     util::{
         builder::MessageBuilder,
-        constants::{AVATAR_URL, GENERAL_ISSUE, OSU_API_ISSUE, OSU_WEB_ISSUE},
-        matcher, numbers,
+        constants::{
+            GENERAL_ISSUE, OSU_API_ISSUE, OSU_QUEUE_FULL, OSU_SESSION_EXPIRED, OSU_UNREACHABLE,
+            OSU_WEB_ISSUE,
+        },
+        matcher::{self, DiscordMessage},
+        numbers,
         osu::{MapIdType, ModSelection},
-        ChannelExt, InteractionCommandExt,
+        ChannelExt, CowUtils, InteractionCommandExt, MessageExt,
     },
-    BotResult, Context,
+    BotResult, Context, CONFIG,
 };
 
-use super::{HasMods, ModsResult};
+use super::{HasMods, ModsResult, ScopeOption, ScoreFilters, ScoreOrder};
+
+/// Waiting for the ratelimit at least this long is worth telling the user about
+const LONG_QUEUE_WAIT: Duration = Duration::from_secs(15);
 
 #[derive(CommandModel, CreateCommand, SlashCommand)]
 #[command(name = "nationalleaderboard")]
 /// Display the national leaderboard of a map (same as `/nlb`)
 pub struct Leaderboard<'a> {
     #[command(help = "Specify a map either by map url or map id.\n\
+        A link to a discord message of this server works too, \
+        in which case the map of that message is used.\n\
         If none is specified, it will search in the recent channel history \
         and pick the first map it can find.")]
-    /// Specify a map url or map id
+    /// Specify a map url, map id, or message link
     map: Option<Cow<'a, str>>,
     #[command(
         help = "Specify mods either directly or through the explicit `+mod!` / `+mod` syntax, \
@@ -41,6 +57,84 @@ pub struct Leaderboard<'a> {
     )]
     /// Specify mods e.g. hdhr or nm
     mods: Option<Cow<'a, str>>,
+    #[command(help = "Choose how the scores should be ordered.\n\
+        If none is specified, the scores are ordered as on the leaderboard.")]
+    /// Choose how the scores should be ordered
+    sort: Option<ScoreOrder>,
+    /// Reverse the resulting score list
+    reverse: Option<bool>,
+    #[command(help = "Choose whose scores should be shown.\n\
+        If none is specified, it will show the national leaderboard.\n\
+        Friend leaderboards show the friends of the account of the bot's first osu! session.")]
+    /// Choose between country, global, or friend leaderboard
+    scope: Option<ScopeOption>,
+    #[command(
+        help = "Choose the country of the national leaderboard by its country code, \
+        e.g. `DE`.\n\
+        Only countries for which the bot has an osu! session are available.\n\
+        If none is specified, the server's default country is used."
+    )]
+    /// Specify the country code of the national leaderboard e.g. DE
+    country: Option<Cow<'a, str>>,
+    #[command(
+        help = "Only show scores that satisfy all given whitespace-separated conditions.\n\
+        Conditions are of the form `key<operator>value` where the key is one of \
+        `acc`, `combo`, `date`, `grade`, `miss`, `name`, `pp`, or `score` \
+        and the operator is one of `<`, `<=`, `=`, `!=`, `>=`, or `>`.\n\
+        Names can also be matched partially via `~`, e.g. `name~peppy`.\n\
+        Additionally, the keywords `fc`, `lazer`, and `stable` can be used, \
+        optionally negated with a leading `!`.\n\
+        Example: `acc>99 miss=0 grade>=S date>2023-01-01 lazer`"
+    )]
+    /// Filter scores e.g. `acc>99 miss=0 grade>=S date>2023-01-01 lazer`
+    filter: Option<Cow<'a, str>>,
+    #[command(help = "Instead of listing the scores, show statistics about them \
+        such as min/avg/max values and the most common mod combinations.")]
+    /// Show statistics about the scores instead of listing them
+    stats: Option<bool>,
+    #[command(help = "Specify an osu! username, user id, or profile url \
+        to start on the page of that user's score and highlight it.\n\
+        If the user is not on the leaderboard, their best score on the map is shown below it.")]
+    /// Highlight the score of a user
+    user: Option<Cow<'a, str>>,
+    #[command(
+        help = "If a mapset is specified, all of its difficulties are summarized \
+        with their top score.\n\
+        To show the full leaderboard of one of them instead, specify the difficulty \
+        either by its name or by its position in that summary.\n\
+        Sorting, stats, user, extended, and detailed require a difficulty."
+    )]
+    /// When specifying a mapset, choose a difficulty by name or position
+    difficulty: Option<Cow<'a, str>>,
+    #[command(
+        min_value = 1,
+        max_value = 50,
+        help = "If no map is specified, pick the n-th most recent map \
+        in the channel history instead of the latest one."
+    )]
+    /// Pick the n-th most recent map in the channel
+    index: Option<u32>,
+    #[command(
+        help = "Extend the leaderboard beyond its top 50 by additionally requesting \
+        the leaderboards of common mod combinations and merging them.\n\
+        Since some scores might still be missing, ranks after the top 50 are only approximate \
+        and marked with `~`.\n\
+        Ignored if mods are specified, other than excluded ones."
+    )]
+    /// Extend the leaderboard beyond the top 50 through mod-filtered leaderboards
+    extended: Option<bool>,
+    #[command(
+        help = "Each score shows its pp, the map's max pp with the score's mods, \
+        and, unless it's a full combo, the pp it would be worth as a full combo \
+        with its misses turned into hits at about the same accuracy. \
+        osu!mania scores have no full combo value since their pp don't depend on combo.\n\
+        Calculated pp marked with `≈` belong to osu!standard scores set with lazer's scoring, \
+        whose slider judgements can't be considered.\n\
+        Showing details additionally lists the aim, speed, accuracy, and flashlight pp \
+        of osu!standard scores."
+    )]
+    /// Show the aim, speed, and acc pp of osu!standard scores
+    detailed: Option<bool>,
 }
 
 #[derive(CommandModel, CreateCommand, SlashCommand)]
@@ -48,9 +142,11 @@ pub struct Leaderboard<'a> {
 /// Display the national leaderboard of a map
 pub struct Nlb<'a> {
     #[command(help = "Specify a map either by map url or map id.\n\
+        A link to a discord message of this server works too, \
+        in which case the map of that message is used.\n\
         If none is specified, it will search in the recent channel history \
         and pick the first map it can find.")]
-    /// Specify a map url or map id
+    /// Specify a map url, map id, or message link
     map: Option<Cow<'a, str>>,
     #[command(
         help = "Specify mods either directly or through the explicit `+mod!` / `+mod` syntax, \
@@ -58,31 +154,185 @@ pub struct Nlb<'a> {
     )]
     /// Specify mods e.g. hdhr or nm
     mods: Option<Cow<'a, str>>,
+    #[command(help = "Choose how the scores should be ordered.\n\
+        If none is specified, the scores are ordered as on the leaderboard.")]
+    /// Choose how the scores should be ordered
+    sort: Option<ScoreOrder>,
+    /// Reverse the resulting score list
+    reverse: Option<bool>,
+    #[command(help = "Choose whose scores should be shown.\n\
+        If none is specified, it will show the national leaderboard.\n\
+        Friend leaderboards show the friends of the account of the bot's first osu! session.")]
+    /// Choose between country, global, or friend leaderboard
+    scope: Option<ScopeOption>,
+    #[command(
+        help = "Choose the country of the national leaderboard by its country code, \
+        e.g. `DE`.\n\
+        Only countries for which the bot has an osu! session are available.\n\
+        If none is specified, the server's default country is used."
+    )]
+    /// Specify the country code of the national leaderboard e.g. DE
+    country: Option<Cow<'a, str>>,
+    #[command(
+        help = "Only show scores that satisfy all given whitespace-separated conditions.\n\
+        Conditions are of the form `key<operator>value` where the key is one of \
+        `acc`, `combo`, `date`, `grade`, `miss`, `name`, `pp`, or `score` \
+        and the operator is one of `<`, `<=`, `=`, `!=`, `>=`, or `>`.\n\
+        Names can also be matched partially via `~`, e.g. `name~peppy`.\n\
+        Additionally, the keywords `fc`, `lazer`, and `stable` can be used, \
+        optionally negated with a leading `!`.\n\
+        Example: `acc>99 miss=0 grade>=S date>2023-01-01 lazer`"
+    )]
+    /// Filter scores e.g. `acc>99 miss=0 grade>=S date>2023-01-01 lazer`
+    filter: Option<Cow<'a, str>>,
+    #[command(help = "Instead of listing the scores, show statistics about them \
+        such as min/avg/max values and the most common mod combinations.")]
+    /// Show statistics about the scores instead of listing them
+    stats: Option<bool>,
+    #[command(help = "Specify an osu! username, user id, or profile url \
+        to start on the page of that user's score and highlight it.\n\
+        If the user is not on the leaderboard, their best score on the map is shown below it.")]
+    /// Highlight the score of a user
+    user: Option<Cow<'a, str>>,
+    #[command(
+        help = "If a mapset is specified, all of its difficulties are summarized \
+        with their top score.\n\
+        To show the full leaderboard of one of them instead, specify the difficulty \
+        either by its name or by its position in that summary.\n\
+        Sorting, stats, user, extended, and detailed require a difficulty."
+    )]
+    /// When specifying a mapset, choose a difficulty by name or position
+    difficulty: Option<Cow<'a, str>>,
+    #[command(
+        min_value = 1,
+        max_value = 50,
+        help = "If no map is specified, pick the n-th most recent map \
+        in the channel history instead of the latest one."
+    )]
+    /// Pick the n-th most recent map in the channel
+    index: Option<u32>,
+    #[command(
+        help = "Extend the leaderboard beyond its top 50 by additionally requesting \
+        the leaderboards of common mod combinations and merging them.\n\
+        Since some scores might still be missing, ranks after the top 50 are only approximate \
+        and marked with `~`.\n\
+        Ignored if mods are specified, other than excluded ones."
+    )]
+    /// Extend the leaderboard beyond the top 50 through mod-filtered leaderboards
+    extended: Option<bool>,
+    #[command(
+        help = "Each score shows its pp, the map's max pp with the score's mods, \
+        and, unless it's a full combo, the pp it would be worth as a full combo \
+        with its misses turned into hits at about the same accuracy. \
+        osu!mania scores have no full combo value since their pp don't depend on combo.\n\
+        Calculated pp marked with `≈` belong to osu!standard scores set with lazer's scoring, \
+        whose slider judgements can't be considered.\n\
+        Showing details additionally lists the aim, speed, accuracy, and flashlight pp \
+        of osu!standard scores."
+    )]
+    /// Show the aim, speed, and acc pp of osu!standard scores
+    detailed: Option<bool>,
 }
 
 #[derive(HasMods)]
 struct LeaderboardArgs<'a> {
     map: Option<MapIdType>,
     mods: Option<Cow<'a, str>>,
+    sort: Option<ScoreOrder>,
+    reverse: bool,
+    scope: LeaderboardScope,
+    country: Option<Cow<'a, str>>,
+    filter: Option<Cow<'a, str>>,
+    stats: bool,
+    user: Option<OsuUserId>,
+    difficulty: Option<Cow<'a, str>>,
+    message: Option<DiscordMessage>,
+    /// Zero-based index into the maps of the channel history
+    index: usize,
+    extended: bool,
+    detailed: bool,
+    style: PaginationStyle,
 }
 
 impl<'m> LeaderboardArgs<'m> {
     fn args(msg: &Message, args: Args<'m>) -> Result<Self, String> {
         let mut map = None;
         let mut mods = None;
+        let mut sort = None;
+        let mut reverse = false;
+        let mut scope = LeaderboardScope::default();
+        let mut country = None;
+        let mut filter = String::new();
+        let mut stats = false;
+        let mut user = None;
+        let mut difficulty = None;
+        let mut message = None;
+        let mut index = 0;
+        let mut extended = false;
+        let mut detailed = false;
+        let mut style = PaginationStyle::default();
 
-        for arg in args.take(2) {
+        for arg in args {
             if let Some(id) = matcher::get_osu_map_id(arg)
                 .map(MapIdType::Map)
                 .or_else(|| matcher::get_osu_mapset_id(arg).map(MapIdType::Set))
             {
                 map = Some(id);
+            } else if let Some(link) = matcher::get_discord_message(arg) {
+                message = Some(link);
+            } else if let Some(n) = arg.strip_prefix('^') {
+                index = parse_index(n)?;
             } else if matcher::get_mods(arg).is_some() {
                 mods = Some(arg.into());
+            } else if let Some(user_id) = matcher::get_osu_user_id(arg) {
+                user = Some(user_id);
+            } else if arg.eq_ignore_ascii_case("reverse") || arg.eq_ignore_ascii_case("rev") {
+                reverse = true;
+            } else if arg.eq_ignore_ascii_case("stats") {
+                stats = true;
+            } else if arg.eq_ignore_ascii_case("extended") || arg.eq_ignore_ascii_case("ext") {
+                extended = true;
+            } else if arg.eq_ignore_ascii_case("detailed") || arg.eq_ignore_ascii_case("details") {
+                detailed = true;
+            } else if arg.eq_ignore_ascii_case("reactions") {
+                style = PaginationStyle::Reactions;
+            } else if let Some((key, value)) = arg.split_once('=') {
+                match key.cow_to_ascii_lowercase().as_ref() {
+                    "sort" | "order" => {
+                        sort = Some(value.cow_to_ascii_lowercase().parse::<ScoreOrder>()?)
+                    }
+                    "reverse" | "rev" => match value.cow_to_ascii_lowercase().as_ref() {
+                        "true" | "t" | "1" => reverse = true,
+                        "false" | "f" | "0" => reverse = false,
+                        _ => {
+                            let content =
+                                "Failed to parse `reverse`. Must be either `true` or `false`.";
+
+                            return Err(content.to_owned());
+                        }
+                    },
+                    // Only `user=...` highlights, other operators make it a name filter
+                    "user" | "u" if !value.starts_with('=') => user = Some(parse_user(value)),
+                    "difficulty" | "diff" => difficulty = Some(value.into()),
+                    "index" | "i" => index = parse_index(value)?,
+                    "scope" | "type" => {
+                        scope = value
+                            .cow_to_ascii_lowercase()
+                            .parse::<ScopeOption>()?
+                            .into()
+                    }
+                    "country" | "c" => country = Some(value.into()),
+                    // Something like `miss=0` is a filter condition
+                    _ => push_filter_term(&mut filter, arg),
+                }
+            } else if ScoreFilters::is_term(arg) {
+                push_filter_term(&mut filter, arg);
             } else {
                 let content = format!(
                     "Failed to parse `{arg}`.\n\
-                    Must be either a map id, map url, or mods.",
+                    Must be either a map id, map url, message link, mods, `^n`, `sort=...`, \
+                    `scope=...`, `country=...`, `user=...`, `diff=...`, `reverse`, `stats`, \
+                    `extended`, `detailed`, `reactions`, or a filter condition like `acc>99`.",
                 );
 
                 return Err(content);
@@ -98,8 +348,76 @@ impl<'m> LeaderboardArgs<'m> {
             map = Some(id);
         }
 
-        Ok(Self { map, mods })
+        Ok(Self {
+            map,
+            mods,
+            sort,
+            reverse,
+            scope,
+            country,
+            filter: (!filter.is_empty()).then_some(Cow::Owned(filter)),
+            stats,
+            user,
+            difficulty,
+            message,
+            index,
+            extended,
+            detailed,
+            style,
+        })
+    }
+
+    /// Given options that only apply to the leaderboard of a single difficulty
+    fn mapset_unsupported(&self) -> Vec<&'static str> {
+        let options = [
+            ("sort", self.sort.is_some()),
+            ("reverse", self.reverse),
+            ("stats", self.stats),
+            ("user", self.user.is_some()),
+            ("extended", self.extended),
+            ("detailed", self.detailed),
+        ];
+
+        options
+            .into_iter()
+            .filter_map(|(name, given)| given.then_some(name))
+            .collect()
+    }
+}
+
+/// Parse the one-based index of `^n` or `index=n`
+fn parse_index(n: &str) -> Result<usize, String> {
+    match n.parse::<usize>() {
+        Ok(n @ 1..=50) => Ok(n - 1),
+        _ => Err(format!(
+            "Failed to parse index `{n}`. Must be a number between 1 and 50."
+        )),
+    }
+}
+
+/// Parse a username, user id, or profile url
+fn parse_user(arg: &str) -> OsuUserId {
+    matcher::get_osu_user_id(arg)
+        .or_else(|| arg.parse().ok().map(OsuUserId::Id))
+        .unwrap_or_else(|| OsuUserId::Name(arg.into()))
+}
+
+fn is_user(score: &Score, user_id: &OsuUserId) -> bool {
+    match user_id {
+        OsuUserId::Id(id) => score.user_id == *id,
+        OsuUserId::Name(name) => score
+            .user
+            .as_ref()
+            .map_or(false, |user| user.username.eq_ignore_ascii_case(name)),
+    }
+}
+
+fn push_filter_term(filter: &mut String, term: &str) {
+    if !filter.is_empty() {
+        filter.push(' ');
     }
+
+    filter.push_str(term);
 }
 
 macro_rules! impl_from_lb {
@@ -109,6 +427,8 @@ macro_rules! impl_from_lb {
                 type Error = &'static str;
 
                 fn try_from(args: $ty<'a>) -> Result<Self, Self::Error> {
+                    let mut message = None;
+
                     let map = match args.map {
                         Some(map) => {
                             if let Some(id) = matcher::get_osu_map_id(&map)
@@ -116,9 +436,14 @@ macro_rules! impl_from_lb {
                                 .or_else(|| matcher::get_osu_mapset_id(&map).map(MapIdType::Set))
                             {
                                 Some(id)
+                            } else if let Some(link) = matcher::get_discord_message(&map) {
+                                message = Some(link);
+
+                                None
                             } else {
                                 return Err(
-                                    "Failed to parse map url. Be sure you specify a valid map id or url to a map.",
+                                    "Failed to parse map url. Be sure you specify a valid map id, \
+                                    url to a map, or link to a message containing a map.",
                                 );
                             }
                         }
@@ -128,6 +453,19 @@ macro_rules! impl_from_lb {
                     Ok(Self {
                         map,
                         mods: args.mods,
+                        sort: args.sort,
+                        reverse: args.reverse.unwrap_or(false),
+                        scope: args.scope.map(LeaderboardScope::from).unwrap_or_default(),
+                        country: args.country,
+                        filter: args.filter,
+                        stats: args.stats.unwrap_or(false),
+                        user: args.user.as_deref().map(parse_user),
+                        difficulty: args.difficulty,
+                        message,
+                        index: args.index.map_or(0, |n| n as usize - 1),
+                        extended: args.extended.unwrap_or(false),
+                        detailed: args.detailed.unwrap_or(false),
+                        style: PaginationStyle::Buttons,
                     })
                 }
             }
@@ -143,10 +481,60 @@ impl_from_lb!(Leaderboard, Nlb);
     "Display the national leaderboard of a given map.\n\
     If no map is given, I will choose the last map \
     I can find in the embeds of this channel.\n\
-    Mods can be specified."
+    To pick an earlier map instead, specify `^n` for the n-th most recent one, e.g. `^2`.\n\
+    Instead of a map, you can also give a link to a message of this server that contains a map.\n\
+    Mods can be specified.\n\
+    To order the scores differently, specify `sort=...` with one of \
+    `acc`, `bpm`, `combo`, `date`, `len`, `misses`, `pp`, `score`, or `stars`.\n\
+    Add `reverse` to reverse the resulting order.\n\
+    To show the global or friend leaderboard instead of the national one, \
+    specify `scope=global` or `scope=friend`. \
+    Friend leaderboards show the friends of the account of my first osu! session.\n\
+    To show the national leaderboard of another country than the server's default one, \
+    specify `country=...` with the country code, e.g. `country=DE`. \
+    Only countries for which I have an osu! session are available.\n\
+    Scores can be filtered through conditions of the form `key<operator>value` \
+    where the key is one of `acc`, `combo`, `date`, `grade`, `miss`, `name`, `pp`, or `score` \
+    and the operator is one of `<`, `<=`, `=`, `!=`, `>=`, or `>`, e.g. `acc>99 miss=0 grade>=S`.\n\
+    Names can also be matched partially via `~`, e.g. `name~peppy`.\n\
+    Additionally, the keywords `fc`, `lazer`, and `stable` can be used, \
+    optionally negated with a leading `!`.\n\
+    Add `stats` to show statistics about the scores instead of listing them.\n\
+    To highlight a user's score, specify `user=...` with a username or user id, \
+    or just give the user's profile url.\n\
+    If a mapset url is given, I will summarize the top score of each difficulty. \
+    To show the full leaderboard of one of them, specify `diff=...` \
+    with either the difficulty's name or its position in the summary. \
+    Sorting, `stats`, `user`, `extended`, and `detailed` only work for a single difficulty.\n\
+    Add `extended` to go beyond the top 50 by merging the leaderboards of common mod combinations. \
+    Ranks that are only approximate because scores might be missing are marked with `~`.\n\
+    Next to a score's pp and the map's max pp, the pp of the score as a full combo are shown \
+    if it isn't one already, except for osu!mania. \
+    Calculated pp marked with `≈` are approximate since slider judgements \
+    of lazer-scoring scores can't be considered. \
+    Add `detailed` to also show the aim, speed, and accuracy pp of osu!standard scores.\n\
+    Pages are navigated through buttons, add `reactions` to use reactions instead."
+)]
+#[usage(
+    "[map url / map id / message link / ^n] [mods] [sort=acc/combo/date/misses/pp/...] [reverse] \
+    [scope=country/global/friend] [country=...] [filter conditions] [stats] [user=...] \
+    [diff=...] [extended] [detailed] [reactions]"
+)]
+#[example(
+    "2240404",
+    "^2 +hd",
+    "https://osu.ppy.sh/beatmapsets/902425#osu/2240404",
+    "2240404 +hd sort=acc reverse",
+    "2240404 scope=global",
+    "2240404 country=DE",
+    "2240404 acc>99 miss=0 date>2023-01-01 lazer",
+    "2240404 stats",
+    "2240404 user=peppy",
+    "2240404 extended",
+    "2240404 detailed",
+    "https://osu.ppy.sh/beatmapsets/1068497",
+    "https://osu.ppy.sh/beatmapsets/1068497 diff=3"
 )]
-#[usage("[map url / map id] [mods]")]
-#[example("2240404", "https://osu.ppy.sh/beatmapsets/902425#osu/2240404")]
 #[alias("nlb")]
 #[group(AllModes)]
 async fn prefix_nationalleaderboard(
@@ -208,14 +596,127 @@ async fn leaderboard(
         }
     };
 
+    let filters = match args.filter.as_deref().map(str::parse::<ScoreFilters>) {
+        Some(Ok(filters)) => Some(filters),
+        Some(Err(content)) => return orig.error(&ctx, content).await,
+        None => None,
+    };
+
+    let country = match resolve_country(args.country.as_deref(), orig.guild_id()) {
+        Ok(country) => country,
+        Err(content) => return orig.error(&ctx, content).await,
+    };
+
     let owner = orig.user_id()?;
 
-    let map_id = match args.map {
+    let requester = match orig.guild_id() {
+        Some(guild_id) => Requester::Guild(guild_id),
+        None => Requester::User(owner),
+    };
+
+    let map = match (args.map, args.message) {
+        (Some(map), _) => Some(map),
+        (None, Some(link)) => {
+            // Messages of other servers or DMs must not be leaked
+            let same_place = match (link.guild_id, orig.guild_id()) {
+                (Some(linked), Some(guild_id)) => linked == guild_id,
+                (None, None) => link.channel_id == orig.channel_id(),
+                (Some(_), None) | (None, Some(_)) => false,
+            };
+
+            if !same_place {
+                let content = "Only messages of this server can be linked \
+                    or, in DMs, messages of our DM channel.";
+
+                return orig.error(&ctx, content).await;
+            }
+
+            let msg = match ctx.retrieve_message(link.channel_id, link.msg_id).await {
+                Ok(msg) => msg,
+                Err(err) => {
+                    // Most likely a mistyped or deleted link so not worth a warning
+                    let wrap = "failed to retrieve linked message";
+                    debug!("{:?}", Report::new(err).wrap_err(wrap));
+
+                    let content = "Failed to retrieve the linked message. \
+                        Be sure it exists and I have access to it.";
+
+                    return orig.error(&ctx, content).await;
+                }
+            };
+
+            match MapIdType::from_msg(&msg) {
+                Some(id) => Some(id),
+                None => {
+                    let content = "Could not find a map in the linked message";
+
+                    return orig.error(&ctx, content).await;
+                }
+            }
+        }
+        (None, None) => None,
+    };
+
+    let map_id = match map {
         Some(MapIdType::Map(id)) => id,
-        Some(MapIdType::Set(_)) => {
-            let content = "Looks like you gave me a mapset id, I need a map id though";
+        Some(MapIdType::Set(mapset_id)) => {
+            let unsupported = args.mapset_unsupported();
 
-            return orig.error(&ctx, content).await;
+            if args.difficulty.is_none() && !unsupported.is_empty() {
+                let mut content = String::from("The options ");
+
+                for (i, option) in unsupported.iter().enumerate() {
+                    if i > 0 {
+                        content.push_str(", ");
+                    }
+
+                    let _ = write!(content, "`{option}`");
+                }
+
+                content.push_str(
+                    " can't be used for a mapset summary. \
+                    Specify a difficulty via `diff=...` to use them.",
+                );
+
+                return orig.error(&ctx, content).await;
+            }
+
+            let mapset = match ctx.client().get_beatmapset(mapset_id).await {
+                Ok(mapset) => mapset,
+                Err(err) if err.is_not_found() => {
+                    let content = format!("Could not find mapset with id `{mapset_id}`");
+
+                    return orig.error(&ctx, content).await;
+                }
+                Err(err) => {
+                    let _ = orig.error(&ctx, OSU_API_ISSUE).await;
+
+                    return Err(err.into());
+                }
+            };
+
+            let Some(ref difficulty) = args.difficulty else {
+                let mapset_fut = mapset_leaderboard(
+                    ctx, orig, mapset, mods, filters, args.scope, country, requester,
+                );
+
+                return mapset_fut.await;
+            };
+
+            let maps = sorted_difficulties(&mapset);
+
+            match find_difficulty(&maps, difficulty) {
+                Some(map) => map.map_id,
+                None => {
+                    let content = format!(
+                        "Could not find a difficulty `{difficulty}` in the mapset.\n\
+                        Specify either its name or its position between 1 and {}.",
+                        maps.len()
+                    );
+
+                    return orig.error(&ctx, content).await;
+                }
+            }
         }
         None => {
             let msgs = match ctx.retrieve_channel_history(orig.channel_id()).await {
@@ -227,8 +728,16 @@ async fn leaderboard(
                 }
             };
 
-            match MapIdType::map_from_msgs(&msgs, 0) {
+            match MapIdType::map_from_msgs(&msgs, args.index) {
                 Some(id) => id,
+                None if args.index > 0 => {
+                    let content = format!(
+                        "Could not find {} maps in the recent channel history",
+                        args.index + 1
+                    );
+
+                    return orig.error(&ctx, content).await;
+                }
                 None => {
                     let content = "No beatmap specified and none found in recent channel history. \
                         Try specifying a map either by url to the map, or just by map id.";
@@ -240,9 +749,13 @@ async fn leaderboard(
     };
 
     // Retrieving the beatmap
-    let mut map = match ctx.osu().beatmap().map_id(map_id).await {
-        Ok(map) => map,
-        Err(OsuError::NotFound) => {
+    let (mut map, map_notice) = match ctx.beatmap(map_id).await {
+        Ok(map) => {
+            let notice = map.notice();
+
+            (map.value, notice)
+        }
+        Err(err) if err.is_not_found() => {
             let content = format!(
                 "Could not find beatmap with id `{map_id}`. \
                 Did you give me a mapset id instead of a map id?",
@@ -265,17 +778,66 @@ async fn leaderboard(
     }
 
     // Retrieve the map's leaderboard
-    let scores_future = ctx.client().get_leaderboard(
-        map_id,
-        match mods {
-            Some(ModSelection::Exclude(_)) | None => None,
-            Some(ModSelection::Include(ref m)) | Some(ModSelection::Exact(ref m)) => Some(m),
-        },
-        map.mode,
-    );
+    let request_mods = match mods {
+        Some(ModSelection::Exclude(_)) | None => None,
+        Some(ModSelection::Include(ref m)) | Some(ModSelection::Exact(ref m)) => Some(m),
+    };
+
+    let extended = args.extended && request_mods.is_none();
+
+    let requests = if extended {
+        ctx.extended_leaderboard_requests(map_id, map.mode, args.scope, country)
+    } else {
+        ctx.leaderboard_requests(map_id, request_mods, map.mode, args.scope, country)
+    };
 
-    let scores = match scores_future.await {
-        Ok(scores) => scores,
+    let queue_notice = notify_queue_position(
+        &ctx, &orig, requests, extended, args.scope, country, requester,
+    )
+    .await;
+
+    let scores_res = if extended {
+        ctx.extended_leaderboard(map_id, map.mode, args.scope, country, requester)
+            .await
+            .map(|leaderboard| {
+                let notice = leaderboard.notice();
+                let ExtendedLeaderboard {
+                    scores,
+                    uncertain_below,
+                } = leaderboard.value;
+
+                (scores, uncertain_below, notice)
+            })
+    } else {
+        ctx.leaderboard(
+            map_id,
+            request_mods,
+            map.mode,
+            args.scope,
+            country,
+            requester,
+        )
+        .await
+        .map(|scores| {
+            let notice = scores.notice();
+
+            (scores.value, None, notice)
+        })
+    };
+
+    remove_queue_notice(&ctx, queue_notice).await;
+
+    let (mut scores, uncertain_below, scores_notice) = match scores_res {
+        Ok(tuple) => tuple,
+        Err(CustomClientError::SessionExpired) => {
+            return orig.error(&ctx, OSU_SESSION_EXPIRED).await;
+        }
+        Err(CustomClientError::Unreachable(_)) => {
+            return orig.error(&ctx, OSU_UNREACHABLE).await;
+        }
+        Err(CustomClientError::QueueFull) => {
+            return orig.error(&ctx, OSU_QUEUE_FULL).await;
+        }
         Err(err) => {
             let _ = orig.error(&ctx, OSU_WEB_ISSUE).await;
 
@@ -283,24 +845,118 @@ async fn leaderboard(
         }
     };
 
+    // The leaderboard request can only handle some selections so the
+    // scores still need to be filtered manually
+    if let Some(ref selection) = mods {
+        scores.retain(|score| selection.matches(&score.mods, map.mode));
+    }
+
+    if let Some(ref filters) = filters {
+        let mut pps = displayed_pps(&ctx, filters, &map, &scores)
+            .await
+            .into_iter();
+
+        scores
+            .retain(|score| filters.matches(score, map.mode, map.max_combo, pps.next().flatten()));
+    }
+
+    let sort = match args.sort {
+        Some(sort) => Some(sort),
+        None if args.reverse => Some(ScoreOrder::Score),
+        None => None,
+    };
+
+    if let Some(sort) = sort {
+        if let Err(err) = sort.apply(&ctx, &mut scores, &map).await {
+            warn!(
+                "{:?}",
+                Report::new(err).wrap_err("failed to sort leaderboard")
+            );
+        }
+
+        if args.reverse {
+            scores.reverse();
+        }
+    }
+
     let amount = scores.len();
 
+    // Only national leaderboards belong to a country
+    let flag_country = country.filter(|_| args.scope == LeaderboardScope::Country);
+
     // Accumulate all necessary data
-    let first_place_icon = scores.first().map(|s| format!("{AVATAR_URL}{}", s.user_id));
+    let avatar_url = &CONFIG.get().unwrap().urls.avatar;
+    let first_place_icon = scores.first().map(|s| format!("{avatar_url}{}", s.user_id));
+
+    if args.stats {
+        let embed_fut = LeaderboardStatsEmbed::new(
+            &ctx,
+            &map,
+            &scores,
+            &first_place_icon,
+            args.scope,
+            flag_country,
+        );
+
+        let embed_data = match embed_fut.await {
+            Ok(data) => data,
+            Err(err) => {
+                let _ = orig.error(&ctx, GENERAL_ISSUE).await;
+
+                return Err(err);
+            }
+        };
+
+        // Empty content so an interaction's queue notice is replaced too
+        let builder = MessageBuilder::new().content("").embed(embed_data.build());
+        orig.create_message(&ctx, &builder).await?;
+
+        return Ok(());
+    }
+
+    let user = match args.user {
+        Some(ref user_id) => match scores.iter().position(|score| is_user(score, user_id)) {
+            Some(idx) => Some(LeaderboardUser::Listed(idx)),
+            None => {
+                unlisted_user(
+                    &ctx,
+                    &map,
+                    user_id,
+                    mods.as_ref(),
+                    filters.as_ref(),
+                    args.scope,
+                    country,
+                )
+                .await
+            }
+        },
+        None => None,
+    };
 
     let pages = numbers::div_euclid(10, scores.len());
 
+    // Start on the page of the user's score
+    let start_idx = match user {
+        Some(LeaderboardUser::Listed(idx)) => numbers::last_multiple(10, idx + 1),
+        Some(LeaderboardUser::Unlisted { .. }) | None => 0,
+    };
+
     let data_fut = LeaderboardEmbed::new(
         &map,
         if scores.is_empty() {
             None
         } else {
-            Some(scores.iter().take(10))
+            Some(scores.iter().skip(start_idx).take(10))
         },
         &first_place_icon,
-        0,
+        start_idx,
         &ctx,
-        (1, pages),
+        (start_idx / 10 + 1, pages),
+        args.scope,
+        flag_country,
+        user.as_ref(),
+        uncertain_below,
+        args.detailed,
     );
 
     let embed_data = match data_fut.await {
@@ -313,8 +969,46 @@ async fn leaderboard(
     };
 
     // Sending the embed
-    let content =
-        format!("I found {amount} scores with the specified mods on the map's leaderboard");
+    let mut content = format!(
+        "I found {amount} scores with the specified mods{} on the map's {} leaderboard",
+        if filters.is_some() {
+            " and filters"
+        } else {
+            ""
+        },
+        args.scope.name().to_lowercase()
+    );
+
+    if let Some(sort) = args.sort {
+        let _ = write!(content, ", sorted by {}", sort.name());
+    }
+
+    if args.reverse {
+        content.push_str(" (reversed)");
+    }
+
+    if let (Some(user_id), None) = (&args.user, &user) {
+        let _ = write!(
+            content,
+            "\nCould not find a score of `{user_id}` on the map{}",
+            if mods.is_some() || filters.is_some() {
+                " matching the specified mods and filters"
+            } else {
+                ""
+            }
+        );
+    }
+
+    if scores
+        .iter()
+        .any(|score| uncertain_below.map_or(false, |value| score.score < value))
+    {
+        content.push_str("\nRanks marked with `~` are approximate since scores might be missing");
+    }
+
+    if let Some(notice) = scores_notice.or(map_notice) {
+        let _ = write!(content, "\n:warning: {notice}");
+    }
 
     let embed = embed_data.build();
     let builder = MessageBuilder::new().content(content).embed(embed);
@@ -328,10 +1022,392 @@ async fn leaderboard(
     let response = response_raw.model().await?;
 
     // Pagination
-    let pagination =
-        LeaderboardPagination::new(response, map, scores, first_place_icon, Arc::clone(&ctx));
+    let pagination = LeaderboardPagination::new(
+        response,
+        map,
+        scores,
+        first_place_icon,
+        args.scope,
+        flag_country,
+        user,
+        uncertain_below,
+        args.detailed,
+        Arc::clone(&ctx),
+    );
 
-    pagination.start(ctx, owner, 60);
+    pagination.start(ctx, owner, args.style, 60);
 
     Ok(())
 }
+
+/// The country of the national leaderboard; the requested one,
+/// the guild's default one, or the bot's default one in that order
+fn resolve_country(
+    requested: Option<&str>,
+    guild_id: Option<Id<GuildMarker>>,
+) -> Result<Option<&'static str>, String> {
+    let config = CONFIG.get().unwrap();
+    let countries = config.session_countries();
+
+    let Some(requested) = requested else {
+        let guild_country = guild_id.and_then(|guild_id| config.countries.guilds.get(&guild_id));
+
+        return Ok(guild_country
+            .or(config.countries.default.as_ref())
+            .map(String::as_str));
+    };
+
+    if let Some(country) = countries
+        .iter()
+        .copied()
+        .find(|country| country.eq_ignore_ascii_case(requested))
+    {
+        return Ok(Some(country));
+    }
+
+    let content = if countries.is_empty() {
+        format!(
+            "Cannot show the national leaderboard of `{requested}`, \
+            only the one of my osu! session's country is available"
+        )
+    } else {
+        format!(
+            "I have no osu! session for the country `{requested}`.\n\
+            Available countries are: `{}`",
+            countries.join("`, `")
+        )
+    };
+
+    Err(content)
+}
+
+/// The user's best score on the map if it's missing from the leaderboard
+/// but satisfies the same mods, filters, and country as the listed scores.
+///
+/// Skipped for friend leaderboards since the user might not be a friend.
+async fn unlisted_user(
+    ctx: &Context,
+    map: &BeatmapExtended,
+    user_id: &OsuUserId,
+    mods: Option<&ModSelection>,
+    filters: Option<&ScoreFilters>,
+    scope: LeaderboardScope,
+    country: Option<&str>,
+) -> Option<LeaderboardUser> {
+    if scope == LeaderboardScope::Friend {
+        return None;
+    }
+
+    let user_score_fut = ctx
+        .client()
+        .get_beatmap_user_score(map.map_id, user_id, map.mode);
+
+    let user_score = match user_score_fut.await {
+        Ok(user_score) => user_score,
+        Err(err) if err.is_not_found() => return None,
+        Err(err) => {
+            let wrap = "failed to get user score";
+            warn!("{:?}", Report::new(err).wrap_err(wrap));
+
+            return None;
+        }
+    };
+
+    let score = user_score.score;
+
+    let same_country = match (scope, country) {
+        (LeaderboardScope::Country, Some(country)) => score.user.as_ref().map_or(false, |user| {
+            user.country_code.eq_ignore_ascii_case(country)
+        }),
+        _ => true,
+    };
+
+    let matches =
+        same_country && mods.map_or(true, |selection| selection.matches(&score.mods, map.mode));
+
+    let matches = match filters {
+        Some(filters) if matches => {
+            let pps = displayed_pps(ctx, filters, map, std::slice::from_ref(&score)).await;
+
+            filters.matches(&score, map.mode, map.max_combo, pps[0])
+        }
+        _ => matches,
+    };
+
+    matches.then(|| LeaderboardUser::Unlisted {
+        score: Box::new(score),
+        global_pos: user_score.pos,
+    })
+}
+
+/// The pp that each score is displayed with, for filtering by pp.
+///
+/// Only calculated if the filters depend on pp, otherwise osu!'s values are kept.
+async fn displayed_pps(
+    ctx: &Context,
+    filters: &ScoreFilters,
+    map: &BeatmapExtended,
+    scores: &[Score],
+) -> Vec<Option<f32>> {
+    let mut calc = None;
+
+    if filters.needs_pp() && !scores.is_empty() {
+        match PpCalculator::new(ctx, map.map_id).await {
+            Ok(map_calc) => calc = Some(map_calc),
+            Err(err) => warn!("{:?}", Report::new(err)),
+        }
+    }
+
+    scores
+        .iter()
+        .map(|score| match calc {
+            Some(ref mut calc) => Some(score_pp(calc, score)),
+            None => score.pp,
+        })
+        .collect()
+}
+
+/// Let the user know if their requests have to wait for others' requests for a while
+/// and how many requests an extended leaderboard takes.
+///
+/// Returns the notice if it's a separate message that should be removed afterwards.
+/// The notice of an interaction is its response so it's replaced by the result anyway.
+async fn notify_queue_position(
+    ctx: &Context,
+    orig: &CommandOrigin<'_>,
+    requests: usize,
+    extended: bool,
+    scope: LeaderboardScope,
+    country: Option<&str>,
+    requester: Requester,
+) -> Option<Message> {
+    // Cached leaderboards don't queue up
+    if requests == 0 {
+        return None;
+    }
+
+    let mut content = String::new();
+
+    if extended {
+        let _ = write!(
+            content,
+            "Retrieving the extended leaderboard takes up to {requests} osu! requests"
+        );
+    }
+
+    let (ahead, wait) = ctx.client().wait_estimate(scope, country, requester);
+
+    if wait >= LONG_QUEUE_WAIT {
+        if !content.is_empty() {
+            content.push('\n');
+        }
+
+        let _ = write!(
+            content,
+            "There are {ahead} osu! requests ahead of yours, this might take about {} seconds",
+            wait.as_secs()
+        );
+    }
+
+    if content.is_empty() {
+        return None;
+    }
+
+    let builder = MessageBuilder::new().content(content);
+
+    let response = match orig.create_message(ctx, &builder).await {
+        Ok(response) => response,
+        Err(err) => {
+            let report = Report::new(err).wrap_err("failed to send queue position");
+            warn!("{report:?}");
+
+            return None;
+        }
+    };
+
+    match orig {
+        CommandOrigin::Message { .. } => response.model().await.ok(),
+        CommandOrigin::Interaction { .. } => None,
+    }
+}
+
+/// Delete the queue notice now that the requests went through
+async fn remove_queue_notice(ctx: &Context, notice: Option<Message>) {
+    let Some(notice) = notice else { return };
+
+    if let Err(err) = notice.delete(ctx).await {
+        let report = Report::new(err).wrap_err("failed to delete queue notice");
+        warn!("{report:?}");
+    }
+}
+
+/// Summarize the top score of each difficulty in the mapset
+#[allow(clippy::too_many_arguments)]
+async fn mapset_leaderboard(
+    ctx: Arc<Context>,
+    orig: CommandOrigin<'_>,
+    mapset: BeatmapsetExtended,
+    mods: Option<ModSelection>,
+    filters: Option<ScoreFilters>,
+    scope: LeaderboardScope,
+    country: Option<&'static str>,
+    requester: Requester,
+) -> BotResult<()> {
+    let maps = sorted_difficulties(&mapset);
+    let mut entries = Vec::with_capacity(maps.len().min(MAPSET_DIFF_LIMIT));
+    let mut notice = None;
+
+    let request_mods = match mods {
+        Some(ModSelection::Exclude(_)) | None => None,
+        Some(ModSelection::Include(ref m)) | Some(ModSelection::Exact(ref m)) => Some(m),
+    };
+
+    let requests = maps
+        .iter()
+        .take(MAPSET_DIFF_LIMIT)
+        .map(|map| ctx.leaderboard_requests(map.map_id, request_mods, map.mode, scope, country))
+        .sum();
+
+    let queue_notice =
+        notify_queue_position(&ctx, &orig, requests, false, scope, country, requester).await;
+
+    // One leaderboard request per difficulty unless it's cached;
+    // the client's fair queue takes care of spacing them out
+    for map in maps.iter().take(MAPSET_DIFF_LIMIT) {
+        let scores_fut = ctx.leaderboard(
+            map.map_id,
+            request_mods,
+            map.mode,
+            scope,
+            country,
+            requester,
+        );
+
+        let mut scores = match scores_fut.await {
+            Ok(scores) => {
+                if scores.stale_age.is_some() {
+                    notice = scores.notice();
+                }
+
+                scores.value
+            }
+            Err(err) => {
+                remove_queue_notice(&ctx, queue_notice).await;
+
+                return match err {
+                    CustomClientError::SessionExpired => {
+                        orig.error(&ctx, OSU_SESSION_EXPIRED).await
+                    }
+                    CustomClientError::Unreachable(_) => orig.error(&ctx, OSU_UNREACHABLE).await,
+                    CustomClientError::QueueFull => orig.error(&ctx, OSU_QUEUE_FULL).await,
+                    err => {
+                        let _ = orig.error(&ctx, OSU_WEB_ISSUE).await;
+
+                        Err(err.into())
+                    }
+                };
+            }
+        };
+
+        if let Some(ref selection) = mods {
+            scores.retain(|score| selection.matches(&score.mods, map.mode));
+        }
+
+        if let Some(ref filters) = filters {
+            let mut pps = displayed_pps(&ctx, filters, map, &scores).await.into_iter();
+
+            scores.retain(|score| {
+                filters.matches(score, map.mode, map.max_combo, pps.next().flatten())
+            });
+        }
+
+        let top = scores.into_iter().next();
+        let mut stars = map.stars;
+        let mut pp = top.as_ref().and_then(|score| score.pp);
+
+        // Only prepare the map file if its values are actually needed
+        let need_stars = request_mods.is_some();
+        let need_pp = top.is_some() && pp.is_none();
+
+        if need_stars || need_pp {
+            match PpCalculator::new(&ctx, map.map_id).await {
+                Ok(mut calc) => {
+                    if let Some(m) = request_mods {
+                        stars = calc.mods(m.clone()).stars() as f32;
+                    }
+
+                    if let (Some(score), true) = (&top, need_pp) {
+                        pp = Some(calc.score(score).pp() as f32);
+                    }
+                }
+                Err(err) => warn!("{:?}", Report::new(err)),
+            }
+        }
+
+        entries.push(MapsetLeaderboardEntry {
+            map_id: map.map_id,
+            version: map.version.clone(),
+            mode: map.mode,
+            stars,
+            top: top.map(|score| (score, pp.unwrap_or(0.0))),
+        });
+    }
+
+    remove_queue_notice(&ctx, queue_notice).await;
+
+    let flag_country = country.filter(|_| scope == LeaderboardScope::Country);
+    let embed =
+        MapsetLeaderboardEmbed::new(&mapset, &entries, maps.len(), scope, flag_country).build();
+
+    let mut content = format!(
+        "Top scores of each difficulty{} on the {} leaderboard",
+        if mods.is_some() || filters.is_some() {
+            " matching the specified mods and filters"
+        } else {
+            ""
+        },
+        scope.name().to_lowercase()
+    );
+
+    if let Some(notice) = notice {
+        let _ = write!(content, "\n:warning: {notice}");
+    }
+
+    let builder = MessageBuilder::new().content(content).embed(embed);
+    orig.create_message(&ctx, &builder).await?;
+
+    Ok(())
+}
+
+/// Difficulties of the mapset ordered by mode and star rating
+fn sorted_difficulties(mapset: &BeatmapsetExtended) -> Vec<&BeatmapExtended> {
+    let mut maps: Vec<_> = mapset.maps.iter().flatten().collect();
+
+    maps.sort_unstable_by(|a, b| {
+        (a.mode as u8)
+            .cmp(&(b.mode as u8))
+            .then_with(|| a.stars.total_cmp(&b.stars))
+    });
+
+    maps
+}
+
+/// Find a difficulty either by its 1-based position or by its name
+fn find_difficulty<'m>(
+    maps: &[&'m BeatmapExtended],
+    difficulty: &str,
+) -> Option<&'m BeatmapExtended> {
+    if let Ok(pos) = difficulty.parse::<usize>() {
+        return pos.checked_sub(1).and_then(|idx| maps.get(idx)).copied();
+    }
+
+    let name = difficulty.to_lowercase();
+
+    maps.iter()
+        .find(|map| map.version.to_lowercase() == name)
+        .or_else(|| {
+            maps.iter()
+                .find(|map| map.version.to_lowercase().contains(&name))
+        })
+        .copied()
+}
@@ -1,9 +1,24 @@
+use std::{
+    cmp::Reverse,
+    ops::{AddAssign, Div},
+    str::FromStr,
+};
+
+use hashbrown::HashMap;
+use rosu_v2::prelude::{BeatmapExtended, Score};
 use twilight_interactions::command::{CommandOption, CreateOption};
 
-use crate::util::osu::ModSelection;
+use crate::{
+    core::Context,
+    custom_client::LeaderboardScope,
+    error::PpError,
+    pp::{score_pp, ModSettings, PpCalculator},
+    util::osu::{clock_rate, ModSelection},
+};
 
-pub use self::leaderboard::*;
+pub use self::{filter::ScoreFilters, leaderboard::*};
 
+mod filter;
 mod leaderboard;
 
 pub trait HasMods {
@@ -23,6 +38,52 @@ pub struct MinMaxAvg<N> {
     len: N,
 }
 
+impl<N: Number> MinMaxAvg<N> {
+    pub fn new() -> Self {
+        Self {
+            min: N::max(),
+            max: N::min(),
+            sum: N::zero(),
+            len: N::zero(),
+        }
+    }
+
+    pub fn add(&mut self, n: N) {
+        if self.min > n {
+            self.min = n;
+        }
+
+        if self.max < n {
+            self.max = n;
+        }
+
+        self.sum += n;
+        self.len += N::one();
+    }
+
+    pub fn min(&self) -> N {
+        self.min
+    }
+
+    pub fn max(&self) -> N {
+        self.max
+    }
+
+    pub fn avg(&self) -> N {
+        if self.len == N::zero() {
+            N::zero()
+        } else {
+            self.sum / self.len
+        }
+    }
+}
+
+impl<N: Number> Default for MinMaxAvg<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl From<MinMaxAvg<f32>> for MinMaxAvg<u32> {
     fn from(other: MinMaxAvg<f32>) -> Self {
         Self {
@@ -34,6 +95,39 @@ impl From<MinMaxAvg<f32>> for MinMaxAvg<u32> {
     }
 }
 
+pub trait Number: AddAssign + Copy + Div<Output = Self> + PartialOrd {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn max() -> Self;
+    fn min() -> Self;
+}
+
+macro_rules! impl_number {
+    ($($ty:ty: $one:literal),*) => {
+        $(
+            impl Number for $ty {
+                fn zero() -> Self {
+                    <$ty>::default()
+                }
+
+                fn one() -> Self {
+                    $one
+                }
+
+                fn max() -> Self {
+                    <$ty>::MAX
+                }
+
+                fn min() -> Self {
+                    <$ty>::MIN
+                }
+            }
+        )*
+    };
+}
+
+impl_number!(u32: 1, f32: 1.0);
+
 #[derive(Copy, Clone, Eq, PartialEq, CommandOption, CreateOption)]
 pub enum ScoreOrder {
     #[option(name = "Accuracy", value = "acc")]
@@ -50,8 +144,6 @@ pub enum ScoreOrder {
     Misses,
     #[option(name = "PP", value = "pp")]
     Pp,
-    #[option(name = "Map ranked date", value = "ranked_date")]
-    RankedDate,
     #[option(name = "Score", value = "score")]
     Score,
     #[option(name = "Stars", value = "stars")]
@@ -63,3 +155,138 @@ impl Default for ScoreOrder {
         Self::Pp
     }
 }
+
+#[derive(Copy, Clone, Eq, PartialEq, CommandOption, CreateOption)]
+pub enum ScopeOption {
+    #[option(name = "Country", value = "country")]
+    Country,
+    #[option(name = "Global", value = "global")]
+    Global,
+    #[option(name = "Friends", value = "friend")]
+    Friend,
+}
+
+impl From<ScopeOption> for LeaderboardScope {
+    #[inline]
+    fn from(scope: ScopeOption) -> Self {
+        match scope {
+            ScopeOption::Country => Self::Country,
+            ScopeOption::Global => Self::Global,
+            ScopeOption::Friend => Self::Friend,
+        }
+    }
+}
+
+impl FromStr for ScopeOption {
+    type Err = &'static str;
+
+    // ! Make sure the given strings are lower case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scope = match s {
+            "country" | "national" => Self::Country,
+            "global" => Self::Global,
+            "friend" | "friends" => Self::Friend,
+            _ => {
+                return Err("Failed to parse `scope`.\n\
+                Valid scopes are: `country`, `global`, or `friend`")
+            }
+        };
+
+        Ok(scope)
+    }
+}
+
+impl ScoreOrder {
+    /// Sort the scores of a map's leaderboard so that the "best" score comes first.
+    ///
+    /// The sort is stable so scores that compare equal keep their leaderboard order.
+    pub async fn apply(
+        self,
+        ctx: &Context,
+        scores: &mut [Score],
+        map: &BeatmapExtended,
+    ) -> Result<(), PpError> {
+        match self {
+            Self::Acc => scores.sort_by(|a, b| b.accuracy.total_cmp(&a.accuracy)),
+            // All scores are on the same map so only the clock rate matters
+            Self::Bpm => scores.sort_by(|a, b| clock_rate(&b.mods).total_cmp(&clock_rate(&a.mods))),
+            Self::Combo => scores.sort_by_key(|score| Reverse(score.max_combo)),
+            Self::Date => scores.sort_by_key(|score| Reverse(score.ended_at)),
+            Self::Length => {
+                scores.sort_by(|a, b| clock_rate(&a.mods).total_cmp(&clock_rate(&b.mods)))
+            }
+            // Fewer misses are better
+            Self::Misses => scores.sort_by_key(|score| score.statistics.miss),
+            Self::Pp => {
+                let mut calc = PpCalculator::new(ctx, map.map_id).await?;
+
+                let pps: HashMap<_, _> = scores
+                    .iter()
+                    .map(|score| (score.id, score_pp(&mut calc, score)))
+                    .collect();
+
+                scores.sort_by(|a, b| pps[&b.id].total_cmp(&pps[&a.id]));
+            }
+            Self::Score => scores.sort_by_key(|score| Reverse(score.score)),
+            Self::Stars => {
+                let mut calc = PpCalculator::new(ctx, map.map_id).await?;
+                let mut stars = HashMap::new();
+
+                for score in scores.iter() {
+                    stars
+                        .entry(ModSettings::new(&score.mods))
+                        .or_insert_with(|| calc.score(score).stars());
+                }
+
+                scores.sort_by(|a, b| {
+                    let a = stars[&ModSettings::new(&a.mods)];
+                    let b = stars[&ModSettings::new(&b.mods)];
+
+                    b.total_cmp(&a)
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Acc => "accuracy",
+            Self::Bpm => "BPM",
+            Self::Combo => "combo",
+            Self::Date => "date",
+            Self::Length => "length",
+            Self::Misses => "misses",
+            Self::Pp => "pp",
+            Self::Score => "score",
+            Self::Stars => "stars",
+        }
+    }
+}
+
+impl FromStr for ScoreOrder {
+    type Err = &'static str;
+
+    // ! Make sure the given strings are lower case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let order = match s {
+            "acc" | "accuracy" => Self::Acc,
+            "bpm" => Self::Bpm,
+            "combo" => Self::Combo,
+            "date" | "recent" => Self::Date,
+            "len" | "length" => Self::Length,
+            "miss" | "misses" => Self::Misses,
+            "pp" => Self::Pp,
+            "score" => Self::Score,
+            "stars" | "sr" => Self::Stars,
+            _ => {
+                return Err("Failed to parse `sort`.\n\
+                Valid orders are: `acc`, `bpm`, `combo`, `date`, `len`, \
+                `misses`, `pp`, `score`, or `stars`")
+            }
+        };
+
+        Ok(order)
+    }
+}
@@ -1,3 +1,4 @@
 mod ping;
+mod session;
 
-pub use self::ping::*;
+pub use self::{ping::*, session::*};
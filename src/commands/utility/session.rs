@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use command_macros::command;
+
+use crate::{
+    core::Context,
+    custom_client::CustomClientError,
+    util::{builder::MessageBuilder, ChannelExt, MessageExt},
+    BotResult, Error, CONFIG,
+};
+
+#[command]
+#[desc("Replace an osu! session")]
+#[help(
+    "Replace an osu! session cookie without restarting the bot.\n\
+    The first session that was taken out of rotation is replaced, \
+    or the first session if all of them are still fine.\n\
+    If sessions of multiple countries are configured, specify the country code \
+    after the session to only consider that country's sessions.\n\
+    The new session is checked right away by requesting a country leaderboard.\n\
+    Only usable by the bot owner in DMs."
+)]
+#[usage("[osu_session cookie] [country code]")]
+#[flags(ONLY_OWNER, SKIP_DEFER)]
+#[group(Utility)]
+async fn prefix_setsession(ctx: Arc<Context>, msg: &Message, mut args: Args<'_>) -> BotResult<()> {
+    if msg.guild_id.is_some() {
+        // Don't leave the session lying around in a server
+        let _ = msg.delete(&ctx).await;
+        let content = "The session must be provided in DMs";
+        msg.error(&ctx, content).await?;
+
+        return Ok(());
+    }
+
+    let Some(session) = args.next() else {
+        let content = "You must provide the new session";
+        msg.error(&ctx, content).await?;
+
+        return Ok(());
+    };
+
+    let country = args.next().map(str::to_ascii_uppercase);
+
+    match ctx
+        .update_session(session.to_owned(), country.as_deref())
+        .await
+    {
+        Ok(Some(idx)) => {
+            let content = format!(
+                "Updated osu! session #{}, it can access country leaderboards again",
+                idx + 1
+            );
+            let builder = MessageBuilder::new().embed(content);
+            msg.create_message(&ctx, &builder).await?;
+        }
+        Ok(None) => {
+            let countries = CONFIG.get().unwrap().session_countries();
+
+            let content = if countries.is_empty() {
+                "The sessions are not assigned to any country".to_owned()
+            } else {
+                format!(
+                    "There are no sessions for the country `{}`, only for `{}`",
+                    country.unwrap_or_default(),
+                    countries.join("`, `")
+                )
+            };
+
+            msg.error(&ctx, content).await?;
+        }
+        Err(Error::CustomClient(CustomClientError::SessionExpired)) => {
+            let content = "Updated the osu! session but it seems to be expired too";
+            msg.error(&ctx, content).await?;
+        }
+        Err(err) => {
+            let content = "Updated the osu! session but failed to check whether it works";
+            msg.error(&ctx, content).await?;
+
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,452 @@
+use std::{hash::Hash, iter, path::PathBuf, sync::Mutex, time::Duration};
+
+use eyre::Report;
+use futures::future;
+use hashbrown::HashMap;
+use rosu_v2::prelude::{BeatmapExtended, GameMode, GameModsIntermode, Score};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::{fs, time::interval};
+
+use crate::{
+    core::{config::CacheConfig, Context},
+    custom_client::{
+        extended_mods, merge_leaderboards, CustomClientError, ExtendedLeaderboard,
+        LeaderboardScope, Requester, FRIEND_SESSION, LEADERBOARD_LIMIT,
+    },
+    util::mod_equivalence::ModEquivalence,
+};
+
+/// Maximum amount of entries per kind, the oldest ones are evicted first
+const CAPACITY: usize = 2000;
+
+/// Keeps responses around so they don't need to be requested again right away
+/// and can still be shown if osu! fails to respond
+pub struct Cache {
+    leaderboards: TtlCache<LeaderboardKey, Vec<Score>>,
+    maps: TtlCache<u32, BeatmapExtended>,
+    path: Option<&'static PathBuf>,
+    persist_interval: Duration,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+struct LeaderboardKey {
+    map_id: u32,
+    scope: LeaderboardScope,
+    mods: Option<String>,
+    /// Only set for country leaderboards
+    #[serde(default)]
+    country: Option<String>,
+    /// Only set for friend leaderboards since they depend on the session's account
+    #[serde(default)]
+    session: Option<usize>,
+}
+
+impl LeaderboardKey {
+    fn new(
+        map_id: u32,
+        mods: Option<&GameModsIntermode>,
+        scope: LeaderboardScope,
+        country: Option<&str>,
+    ) -> Self {
+        Self {
+            map_id,
+            scope,
+            mods: mods.map(ToString::to_string),
+            country: country
+                .filter(|_| scope == LeaderboardScope::Country)
+                .map(str::to_owned),
+            session: (scope == LeaderboardScope::Friend).then_some(FRIEND_SESSION),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct PersistedCache {
+    leaderboards: Vec<(LeaderboardKey, Entry<Vec<Score>>)>,
+    maps: Vec<(u32, Entry<BeatmapExtended>)>,
+}
+
+impl Cache {
+    pub async fn new(config: &'static CacheConfig) -> Self {
+        let cache = Self {
+            leaderboards: TtlCache::new(config.leaderboard_ttl),
+            maps: TtlCache::new(config.map_ttl),
+            path: config.path.as_ref(),
+            persist_interval: config.persist_interval,
+        };
+
+        let Some(path) = cache.path else {
+            return cache;
+        };
+
+        let persisted = match fs::read(path).await {
+            Ok(bytes) => match serde_json::from_slice::<PersistedCache>(&bytes) {
+                Ok(persisted) => persisted,
+                Err(err) => {
+                    let report = Report::new(err).wrap_err("failed to deserialize cache");
+                    warn!("{report:?}");
+
+                    return cache;
+                }
+            },
+            // Nothing was persisted yet
+            Err(_) => return cache,
+        };
+
+        info!(
+            "Loaded {} leaderboards and {} maps into the cache",
+            persisted.leaderboards.len(),
+            persisted.maps.len()
+        );
+
+        cache.leaderboards.extend(persisted.leaderboards);
+        cache.maps.extend(persisted.maps);
+
+        cache
+    }
+
+    /// Store the cache on disk so it can be loaded on the next startup
+    pub async fn persist(&self) {
+        let Some(path) = self.path else {
+            return;
+        };
+
+        let persisted = PersistedCache {
+            leaderboards: self.leaderboards.entries(),
+            maps: self.maps.entries(),
+        };
+
+        let bytes = match serde_json::to_vec(&persisted) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                let report = Report::new(err).wrap_err("failed to serialize cache");
+
+                return warn!("{report:?}");
+            }
+        };
+
+        // Write to a separate file first so an interrupted write can't corrupt the cache
+        let tmp_path = path.with_extension("tmp");
+
+        let res = match fs::write(&tmp_path, bytes).await {
+            Ok(_) => fs::rename(&tmp_path, path).await,
+            Err(err) => Err(err),
+        };
+
+        match res {
+            Ok(_) => info!("Persisted cache at {path:?}"),
+            Err(err) => {
+                let report = Report::new(err).wrap_err("failed to persist cache");
+                warn!("{report:?}");
+            }
+        }
+    }
+
+    /// Keep persisting the cache so it survives a crash, not just a clean shutdown
+    pub async fn persist_periodically(&self) {
+        if self.path.is_none() || self.persist_interval.is_zero() {
+            return;
+        }
+
+        let mut ticks = interval(self.persist_interval);
+
+        // The first tick completes right away
+        ticks.tick().await;
+
+        loop {
+            ticks.tick().await;
+            self.persist().await;
+        }
+    }
+}
+
+/// A value that may have been served from the cache because the request failed
+pub struct Cached<T> {
+    pub value: T,
+    /// How old the value is if the request failed
+    pub stale_age: Option<Duration>,
+}
+
+impl<T> Cached<T> {
+    fn fresh(value: T) -> Self {
+        Self {
+            value,
+            stale_age: None,
+        }
+    }
+
+    /// Notice to show users if the value is outdated
+    pub fn notice(&self) -> Option<String> {
+        self.stale_age.map(|age| {
+            format!(
+                "osu! failed to respond, showing data from {} minutes ago",
+                age.as_secs() / 60
+            )
+        })
+    }
+}
+
+impl Context {
+    /// How many osu! requests retrieving a map's leaderboard takes, none if it's cached
+    pub fn leaderboard_requests(
+        &self,
+        map_id: u32,
+        mods: Option<&GameModsIntermode>,
+        mode: GameMode,
+        scope: LeaderboardScope,
+        country: Option<&str>,
+    ) -> usize {
+        let key = LeaderboardKey::new(map_id, mods, scope, country);
+
+        if self.cache.leaderboards.is_fresh(&key) {
+            0
+        } else {
+            ModEquivalence::new(mode).variants(mods).len()
+        }
+    }
+
+    /// Retrieve a map's leaderboard, preferably from the cache
+    pub async fn leaderboard(
+        &self,
+        map_id: u32,
+        mods: Option<&GameModsIntermode>,
+        mode: GameMode,
+        scope: LeaderboardScope,
+        country: Option<&str>,
+        requester: Requester,
+    ) -> Result<Cached<Vec<Score>>, CustomClientError> {
+        let key = LeaderboardKey::new(map_id, mods, scope, country);
+
+        let stale = match self.cache.leaderboards.get(&key) {
+            Lookup::Fresh(scores) => return Ok(Cached::fresh(scores)),
+            Lookup::Stale(entry) => Some(entry),
+            Lookup::Missing => None,
+        };
+
+        let res = self
+            .client()
+            .get_leaderboard(map_id, mods, mode, scope, country, requester)
+            .await;
+
+        // A session might have expired even if another one took over
+        self.report_expired_sessions().await;
+
+        let err = match res {
+            Ok(scores) => {
+                self.cache.leaderboards.insert(key, scores.clone());
+
+                return Ok(Cached::fresh(scores));
+            }
+            Err(err) => err,
+        };
+
+        // Outdated scores are only better than nothing if osu! itself failed
+        match stale.filter(|_| err.is_unavailable()) {
+            Some(entry) => {
+                let report = Report::new(err).wrap_err("serving stale leaderboard");
+                warn!("{report:?}");
+
+                Ok(entry.into_cached())
+            }
+            None => Err(err),
+        }
+    }
+
+    /// How many osu! requests retrieving a map's extended leaderboard takes at most
+    pub fn extended_leaderboard_requests(
+        &self,
+        map_id: u32,
+        mode: GameMode,
+        scope: LeaderboardScope,
+        country: Option<&str>,
+    ) -> usize {
+        let extended_mods = extended_mods();
+
+        iter::once(None)
+            .chain(extended_mods.iter().map(Some))
+            .map(|mods| self.leaderboard_requests(map_id, mods, mode, scope, country))
+            .sum()
+    }
+
+    /// Retrieve the leaderboard of a map beyond its regular limit by merging it
+    /// with the leaderboards of common mod combinations, each preferably from the cache
+    pub async fn extended_leaderboard(
+        &self,
+        map_id: u32,
+        mode: GameMode,
+        scope: LeaderboardScope,
+        country: Option<&str>,
+        requester: Requester,
+    ) -> Result<Cached<ExtendedLeaderboard>, CustomClientError> {
+        let top = self
+            .leaderboard(map_id, None, mode, scope, country, requester)
+            .await?;
+
+        // A leaderboard that isn't full already contains all scores
+        if top.value.len() < LEADERBOARD_LIMIT {
+            return Ok(Cached {
+                value: ExtendedLeaderboard {
+                    scores: top.value,
+                    uncertain_below: None,
+                },
+                stale_age: top.stale_age,
+            });
+        }
+
+        // Scores of other mod combinations could be missing below the regular top scores
+        let uncertain_below = top.value.last().map(|score| score.score);
+
+        let extended_mods = extended_mods();
+
+        let requests = extended_mods
+            .iter()
+            .map(|mods| self.leaderboard(map_id, Some(mods), mode, scope, country, requester));
+
+        let extended = future::try_join_all(requests).await?;
+
+        // The merged leaderboard is as outdated as its oldest part
+        let stale_age = iter::once(&top)
+            .chain(&extended)
+            .filter_map(|leaderboard| leaderboard.stale_age)
+            .max();
+
+        let leaderboards = iter::once(top.value)
+            .chain(extended.into_iter().map(|leaderboard| leaderboard.value))
+            .collect();
+
+        let scores = merge_leaderboards(leaderboards, |score| score.user_id, |score| score.score);
+
+        Ok(Cached {
+            value: ExtendedLeaderboard {
+                scores,
+                uncertain_below,
+            },
+            stale_age,
+        })
+    }
+
+    /// Retrieve a map, preferably from the cache
+    pub async fn beatmap(&self, map_id: u32) -> Result<Cached<BeatmapExtended>, CustomClientError> {
+        let stale = match self.cache.maps.get(&map_id) {
+            Lookup::Fresh(map) => return Ok(Cached::fresh(map)),
+            Lookup::Stale(entry) => Some(entry),
+            Lookup::Missing => None,
+        };
+
+        match self.client().get_beatmap(map_id).await {
+            Ok(map) => {
+                self.cache.maps.insert(map_id, map.clone());
+
+                Ok(Cached::fresh(map))
+            }
+            Err(err) => match stale.filter(|_| err.is_unavailable()) {
+                Some(entry) => {
+                    let report = Report::new(err).wrap_err("serving stale beatmap");
+                    warn!("{report:?}");
+
+                    Ok(entry.into_cached())
+                }
+                None => Err(err),
+            },
+        }
+    }
+}
+
+struct TtlCache<K, V> {
+    entries: Mutex<HashMap<K, Entry<V>>>,
+    ttl: Duration,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Entry<V> {
+    value: V,
+    /// Unix timestamp of when the value was requested
+    fetched_at: i64,
+}
+
+impl<V> Entry<V> {
+    fn age(&self) -> Duration {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        Duration::from_secs(now.saturating_sub(self.fetched_at).max(0) as u64)
+    }
+
+    fn into_cached(self) -> Cached<V> {
+        Cached {
+            stale_age: Some(self.age()),
+            value: self.value,
+        }
+    }
+}
+
+enum Lookup<V> {
+    Fresh(V),
+    /// Expired but still usable if a new request fails
+    Stale(Entry<V>),
+    Missing,
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    fn get(&self, key: &K) -> Lookup<V> {
+        match self.entries.lock().unwrap().get(key) {
+            Some(entry) if entry.age() < self.ttl => Lookup::Fresh(entry.value.clone()),
+            Some(entry) => Lookup::Stale(entry.clone()),
+            None => Lookup::Missing,
+        }
+    }
+
+    fn is_fresh(&self, key: &K) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .map_or(false, |entry| entry.age() < self.ttl)
+    }
+
+    fn insert(&self, key: K, value: V) {
+        let entry = Entry {
+            value,
+            fetched_at: OffsetDateTime::now_utc().unix_timestamp(),
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() >= CAPACITY && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.fetched_at)
+                .map(|(key, _)| key.clone());
+
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(key, entry);
+    }
+
+    fn extend(&self, persisted: Vec<(K, Entry<V>)>) {
+        self.entries.lock().unwrap().extend(persisted);
+    }
+
+    fn entries(&self) -> Vec<(K, Entry<V>)> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.clone()))
+            .collect()
+    }
+}
@@ -3,7 +3,7 @@ bitflags::bitflags! {
         // const AUTHORITY   = 1 << 0;
         // const EPHEMERAL   = 1 << 1;
         // const ONLY_GUILDS = 1 << 2;
-        // const ONLY_OWNER  = 1 << 3;
+        const ONLY_OWNER  = 1 << 3;
         const SKIP_DEFER  = 1 << 4;
     }
 }
@@ -25,7 +25,7 @@ impl CommandFlags {
     //     self.contains(CommandFlags::ONLY_GUILDS)
     // }
 
-    // pub fn only_owner(self) -> bool {
-    //     self.contains(CommandFlags::ONLY_OWNER)
-    // }
+    pub fn only_owner(self) -> bool {
+        self.contains(CommandFlags::ONLY_OWNER)
+    }
 }
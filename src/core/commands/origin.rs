@@ -2,7 +2,7 @@ use twilight_http::{Error as HttpError, Response};
 use twilight_model::{
     channel::Message,
     id::{
-        marker::{ChannelMarker, UserMarker},
+        marker::{ChannelMarker, GuildMarker, UserMarker},
         Id,
     },
 };
@@ -29,6 +29,13 @@ impl CommandOrigin<'_> {
         }
     }
 
+    pub fn guild_id(&self) -> Option<Id<GuildMarker>> {
+        match self {
+            CommandOrigin::Message { msg } => msg.guild_id,
+            CommandOrigin::Interaction { command } => command.guild_id,
+        }
+    }
+
     pub fn channel_id(&self) -> Id<ChannelMarker> {
         match self {
             CommandOrigin::Message { msg } => msg.channel_id,
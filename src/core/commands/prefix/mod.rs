@@ -36,6 +36,7 @@ lazy_static::lazy_static! {
             HELP_PREFIX,
             NATIONALLEADERBOARD_PREFIX,
             PING_PREFIX,
+            SETSESSION_PREFIX,
         }
     };
 }
@@ -1,5 +1,7 @@
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, time::Duration};
 
+use hashbrown::HashMap;
+use http::Uri;
 use once_cell::sync::OnceCell;
 use twilight_model::id::{
     marker::{ChannelMarker, GuildMarker, UserMarker},
@@ -11,20 +13,109 @@ use crate::{BotResult, Error};
 pub static CONFIG: OnceCell<BotConfig> = OnceCell::new();
 
 pub struct BotConfig {
+    pub owner: Option<Id<UserMarker>>,
     pub tokens: Tokens,
     pub paths: Paths,
     pub prefixes: Box<[Box<str>]>,
+    pub cache: CacheConfig,
+    pub urls: Urls,
+    pub http: HttpConfig,
+    pub countries: Countries,
+    pub pp_source: PpSource,
+}
+
+/// Which pp are shown for scores that already come with pp from the osu!api
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PpSource {
+    /// Show the api's pp, only calculating pp for scores without any
+    #[default]
+    Api,
+    /// Show the calculated pp with the api's pp next to them
+    Both,
+}
+
+pub struct Countries {
+    /// Country of national leaderboards unless a guild or command specifies another one
+    pub default: Option<String>,
+    /// Default country of specific guilds
+    pub guilds: HashMap<Id<GuildMarker>, String>,
+}
+
+pub struct HttpConfig {
+    pub proxy: ProxyConfig,
+    /// How long establishing a connection may take
+    pub connect_timeout: Duration,
+    /// How long to wait for a response and its body
+    pub read_timeout: Duration,
+}
+
+pub struct ProxyConfig {
+    /// Proxy for plain http requests
+    pub http: Option<Uri>,
+    /// Proxy for https requests, connected to through a `CONNECT` tunnel
+    pub https: Option<Uri>,
+    /// Hosts that should be connected to directly
+    pub no_proxy: Box<[String]>,
+}
+
+pub struct Urls {
+    /// Base of the osu! website, also used for its api
+    pub osu_base: String,
+    /// Base of map thumbnails
+    pub map_thumb: String,
+    /// Base of user avatars
+    pub avatar: String,
+    /// Urls to retrieve .osu files from if the osu! website fails to provide them,
+    /// in order of preference. A `{map_id}` placeholder is replaced with the map id,
+    /// otherwise the id is appended.
+    pub map_mirrors: Box<[String]>,
+}
+
+pub struct CacheConfig {
+    /// How long leaderboards are considered up to date
+    pub leaderboard_ttl: Duration,
+    /// How long beatmap metadata is considered up to date
+    pub map_ttl: Duration,
+    /// File in which the cache is persisted across restarts
+    pub path: Option<PathBuf>,
+    /// How often the cache is persisted while running
+    pub persist_interval: Duration,
 }
 
 pub struct Paths {
     pub maps: PathBuf,
+    pub session: Option<PathBuf>,
 }
 
 pub struct Tokens {
     pub discord: String,
     pub osu_client_id: u64,
     pub osu_client_secret: String,
-    pub osu_session: String,
+    /// Sessions of supporter accounts, used in turns
+    pub osu_sessions: Box<[OsuSession]>,
+}
+
+pub struct OsuSession {
+    /// Uppercase code of the account's country which determines
+    /// the country leaderboards the session can access
+    pub country: Option<String>,
+    pub cookie: String,
+}
+
+impl OsuSession {
+    /// Parse an entry of the form `[country code:]cookie`
+    fn parse(entry: &str, default_country: Option<&str>) -> Self {
+        match entry.split_once(':') {
+            Some((country, cookie)) if is_country_code(country) => Self {
+                country: Some(country.to_ascii_uppercase()),
+                cookie: cookie.to_owned(),
+            },
+            _ => Self {
+                country: default_country.map(str::to_owned),
+                cookie: entry.to_owned(),
+            },
+        }
+    }
 }
 
 impl BotConfig {
@@ -41,17 +132,121 @@ impl BotConfig {
                     e => e,
                 })?;
 
+        let configured_country =
+            env_var_opt::<String>("OSU_COUNTRY")?.map(|country| country.to_ascii_uppercase());
+
+        if let Some(country) = configured_country.as_ref().filter(|c| !is_country_code(c)) {
+            return Err(Error::ParsingEnvVariable {
+                name: "OSU_COUNTRY",
+                value: country.to_owned(),
+                expected: "two-letter country code",
+            });
+        }
+
+        let osu_sessions: Box<[_]> = env_var::<Box<[String]>>("OSU_SESSION")
+            .ok()
+            .filter(|sessions| !sessions.is_empty())
+            .ok_or(Error::MissingEnvVariable("OSU_SESSION"))?
+            .iter()
+            .map(|entry| OsuSession::parse(entry, configured_country.as_deref()))
+            .collect();
+
+        // Sessions without country could otherwise not be told apart from the others
+        let has_country = |session: &OsuSession| session.country.is_some();
+
+        if osu_sessions.iter().any(has_country) && !osu_sessions.iter().all(has_country) {
+            return Err(Error::ParsingEnvVariable {
+                name: "OSU_COUNTRY",
+                value: String::new(),
+                expected: "a country code since only some sessions are prefixed with one",
+            });
+        }
+
+        let has_sessions = |country: &String| {
+            osu_sessions
+                .iter()
+                .any(|session| session.country.as_ref() == Some(country))
+        };
+
+        if let Some(country) = configured_country.as_ref().filter(|c| !has_sessions(*c)) {
+            return Err(Error::ParsingEnvVariable {
+                name: "OSU_COUNTRY",
+                value: country.to_owned(),
+                expected: "country code for which sessions are configured",
+            });
+        }
+
+        let default_country = configured_country.or_else(|| {
+            osu_sessions
+                .iter()
+                .find_map(|session| session.country.clone())
+        });
+
+        let GuildCountries(guild_countries) = env_var_opt("GUILD_COUNTRIES")?.unwrap_or_default();
+
+        if let Some(country) = guild_countries.values().find(|c| !has_sessions(*c)) {
+            return Err(Error::ParsingEnvVariable {
+                name: "GUILD_COUNTRIES",
+                value: country.to_owned(),
+                expected: "country codes for which sessions are configured",
+            });
+        }
+
         let config = BotConfig {
+            owner: env_var_opt("OWNER_USER_ID")?,
             tokens: Tokens {
                 discord: env_var("DISCORD_TOKEN")?,
                 osu_client_id: env_var("OSU_CLIENT_ID")?,
                 osu_client_secret: env_var("OSU_CLIENT_SECRET")?,
-                osu_session: env_var("OSU_SESSION")?,
+                osu_sessions,
             },
             paths: Paths {
                 maps: env_var("MAP_PATH")?,
+                session: env_var_opt("OSU_SESSION_PATH")?,
             },
             prefixes,
+            cache: CacheConfig {
+                leaderboard_ttl: Duration::from_secs(
+                    env_var_opt("CACHE_LEADERBOARD_TTL")?.unwrap_or(300),
+                ),
+                map_ttl: Duration::from_secs(env_var_opt("CACHE_MAP_TTL")?.unwrap_or(3600)),
+                path: env_var_opt("CACHE_PATH")?,
+                persist_interval: Duration::from_secs(
+                    env_var_opt("CACHE_PERSIST_INTERVAL")?.unwrap_or(600),
+                ),
+            },
+            urls: Urls {
+                osu_base: base_url("OSU_BASE_URL", "https://osu.ppy.sh/")?,
+                map_thumb: base_url("MAP_THUMB_URL", "https://b.ppy.sh/thumb/")?,
+                avatar: base_url("AVATAR_URL", "https://a.ppy.sh/")?,
+                map_mirrors: env_var_opt("MAP_MIRRORS")?.unwrap_or_default(),
+            },
+            http: HttpConfig {
+                proxy: ProxyConfig {
+                    http: proxy_url("HTTP_PROXY", "http_proxy")?,
+                    https: proxy_url("HTTPS_PROXY", "https_proxy")?,
+                    no_proxy: env_var_opt::<String>("NO_PROXY")?
+                        .or(env_var_opt("no_proxy")?)
+                        .map(|hosts| {
+                            hosts
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|host| !host.is_empty())
+                                .map(str::to_owned)
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                },
+                connect_timeout: Duration::from_secs(
+                    env_var_opt("HTTP_CONNECT_TIMEOUT")?.unwrap_or(10),
+                ),
+                read_timeout: Duration::from_secs(env_var_opt("HTTP_READ_TIMEOUT")?.unwrap_or(30)),
+            },
+            countries: Countries {
+                default: default_country,
+                guilds: guild_countries,
+            },
+            pp_source: env_var_opt("PP_SOURCE")?.unwrap_or_default(),
         };
 
         if CONFIG.set(config).is_err() {
@@ -60,6 +255,21 @@ impl BotConfig {
 
         Ok(())
     }
+
+    /// Countries for which sessions are configured, in order of appearance
+    pub fn session_countries(&self) -> Vec<&str> {
+        let mut countries = Vec::new();
+
+        let sessions = self.tokens.osu_sessions.iter();
+
+        for country in sessions.filter_map(|session| session.country.as_deref()) {
+            if !countries.contains(&country) {
+                countries.push(country);
+            }
+        }
+
+        countries
+    }
 }
 
 trait EnvKind: Sized {
@@ -87,9 +297,38 @@ env_kind! {
     u64: s => { s.parse().ok() },
     PathBuf: s => { s.parse().ok() },
     String: s => { Some(s.to_owned()) },
+    Uri: s => {
+        // A proxy is commonly given without a scheme
+        if s.contains("://") {
+            s.parse().ok()
+        } else {
+            format!("http://{s}").parse().ok()
+        }
+    },
+    Box<[String]>: s => { Some(s.split_whitespace().map(str::to_owned).collect()) },
     Id<UserMarker>: s => { s.parse().ok().map(Id::new) },
     Id<GuildMarker>: s => { s.parse().ok().map(Id::new) },
     Id<ChannelMarker>: s => { s.parse().ok().map(Id::new) },
+    GuildCountries: s => {
+        let countries = s
+            .split_whitespace()
+            .map(|entry| {
+                let (guild, country) = entry.split_once(':')?;
+                let guild = guild.parse().ok().map(Id::new)?;
+
+                is_country_code(country).then(|| (guild, country.to_ascii_uppercase()))
+            })
+            .collect::<Option<_>>()?;
+
+        Some(GuildCountries(countries))
+    },
+    PpSource: s => {
+        match s.to_ascii_lowercase().as_str() {
+            "api" => Some(PpSource::Api),
+            "both" => Some(PpSource::Both),
+            _ => None,
+        }
+    },
     Prefixes: s => {
         let prefixes = s
             .split_whitespace()
@@ -127,4 +366,46 @@ fn env_var<T: EnvKind>(name: &'static str) -> BotResult<T> {
     })
 }
 
+/// Same as [`env_var`] but a missing or empty variable is not considered an error
+fn env_var_opt<T: EnvKind>(name: &'static str) -> BotResult<Option<T>> {
+    match env::var(name) {
+        Ok(value) if !value.is_empty() => {
+            T::from_str(&value)
+                .map(Some)
+                .ok_or(Error::ParsingEnvVariable {
+                    name,
+                    value,
+                    expected: T::EXPECTED,
+                })
+        }
+        Ok(_) | Err(_) => Ok(None),
+    }
+}
+
+/// Optional proxy url, checking both the upper- and lowercase variable
+fn proxy_url(upper: &'static str, lower: &'static str) -> BotResult<Option<Uri>> {
+    match env_var_opt(upper)? {
+        Some(url) => Ok(Some(url)),
+        None => env_var_opt(lower),
+    }
+}
+
+fn is_country_code(s: &str) -> bool {
+    s.len() == 2 && s.bytes().all(|byte| byte.is_ascii_alphabetic())
+}
+
+/// Optional base url that is guaranteed to end with a slash
+fn base_url(name: &'static str, default: &str) -> BotResult<String> {
+    let mut url = env_var_opt(name)?.unwrap_or_else(|| default.to_owned());
+
+    if !url.ends_with('/') {
+        url.push('/');
+    }
+
+    Ok(url)
+}
+
 struct Prefixes(Box<[Box<str>]>);
+
+#[derive(Default)]
+struct GuildCountries(HashMap<Id<GuildMarker>, String>);
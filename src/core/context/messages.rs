@@ -1,9 +1,12 @@
 use twilight_model::{
     channel::Message,
-    id::{marker::ChannelMarker, Id},
+    id::{
+        marker::{ChannelMarker, MessageMarker},
+        Id,
+    },
 };
 
-use crate::{BotResult, Context, Error};
+use crate::{core::CONFIG, BotResult, Context, Error};
 
 impl Context {
     pub async fn retrieve_channel_history(
@@ -19,4 +22,40 @@ impl Context {
             .await
             .map_err(Error::from)
     }
+
+    pub async fn retrieve_message(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        msg_id: Id<MessageMarker>,
+    ) -> BotResult<Message> {
+        self.http
+            .message(channel_id, msg_id)
+            .await?
+            .model()
+            .await
+            .map_err(Error::from)
+    }
+
+    /// DM the bot owner if one is configured, otherwise just log the content
+    pub async fn dm_owner(&self, content: &str) -> BotResult<()> {
+        let Some(owner) = CONFIG.get().unwrap().owner else {
+            warn!("No owner to notify: {content}");
+
+            return Ok(());
+        };
+
+        let channel = self
+            .http
+            .create_private_channel(owner)
+            .await?
+            .model()
+            .await?;
+
+        self.http
+            .create_message(channel.id)
+            .content(content)?
+            .await?;
+
+        Ok(())
+    }
 }
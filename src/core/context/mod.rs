@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
+use eyre::Report;
 use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
-use rosu_v2::Osu;
 use twilight_gateway::{stream, CloseFrame, Config, EventTypeFlags, Intents, Shard};
 use twilight_http::{client::InteractionClient, Client};
 use twilight_model::{
@@ -10,15 +10,21 @@ use twilight_model::{
 };
 use twilight_standby::Standby;
 
-use crate::{core::CONFIG, custom_client::CustomClient, BotResult, Error as BotError};
+use crate::{
+    core::{cache::Cache, CONFIG},
+    custom_client::{CustomClient, CustomClientError},
+    BotResult, Error as BotError,
+};
 
 use super::BotConfig;
 
 mod messages;
+mod session;
 
 pub struct Context {
     pub http: Arc<Client>,
     pub standby: Standby,
+    pub cache: Cache,
     pub application_id: Id<ApplicationMarker>,
     pub prefixes: Box<[Box<str>]>,
     clients: Clients,
@@ -29,10 +35,6 @@ impl Context {
         self.http.interaction(self.application_id)
     }
 
-    pub fn osu(&self) -> &Osu {
-        &self.clients.osu
-    }
-
     /// Returns the custom client
     pub fn client(&self) -> &CustomClient {
         &self.clients.custom
@@ -44,15 +46,22 @@ impl Context {
         // Connect to discord API
         let (http, application_id) = discord_http(config).await?;
 
-        // Connect to osu! API
-        let osu_client_id = config.tokens.osu_client_id;
-        let osu_client_secret = &config.tokens.osu_client_secret;
-        let osu = Osu::new(osu_client_id, osu_client_secret).await?;
-
         // Log custom client into osu!
         let custom = CustomClient::new(config).await?;
 
-        let clients = Clients::new(osu, custom);
+        match custom.check_sessions().await {
+            Ok(usable) => info!("{usable} osu! session(s) can access country leaderboards"),
+            Err(CustomClientError::SessionExpired) => {
+                error!("All osu! sessions are expired, provide a new one through `setsession`");
+            }
+            Err(err) => {
+                let report = Report::new(err).wrap_err("failed to check osu! sessions");
+                warn!("{report:?}");
+            }
+        }
+
+        let clients = Clients::new(custom);
+        let cache = Cache::new(&config.cache).await;
 
         let shards = discord_gateway(config, &http).await?;
 
@@ -61,6 +70,7 @@ impl Context {
             clients,
             application_id,
             standby: Standby::new(),
+            cache,
             prefixes: config.prefixes.clone(),
         };
 
@@ -91,12 +101,11 @@ impl Context {
 
 struct Clients {
     custom: CustomClient,
-    osu: Osu,
 }
 
 impl Clients {
-    fn new(osu: Osu, custom: CustomClient) -> Self {
-        Self { osu, custom }
+    fn new(custom: CustomClient) -> Self {
+        Self { custom }
     }
 }
 
@@ -0,0 +1,45 @@
+use eyre::Report;
+
+use crate::{BotResult, Context};
+
+impl Context {
+    /// Notify the owner about osu! sessions that expired since the last notification
+    pub async fn report_expired_sessions(&self) {
+        let expired = self.client().unreported_expired_sessions();
+
+        if expired.is_empty() {
+            return;
+        }
+
+        let sessions = expired.join(", ");
+        let usable = self.client().usable_sessions();
+        error!("osu! session(s) {sessions} expired, {usable} left in rotation");
+
+        let content = format!(
+            "osu! session(s) {sessions} expired, {usable} session(s) are still in rotation.\n\
+            DM me `setsession <new session> [country code]` to replace them."
+        );
+
+        if let Err(err) = self.dm_owner(&content).await {
+            let report = Report::new(err).wrap_err("failed to notify owner about expired session");
+            warn!("{report:?}");
+        }
+    }
+
+    /// Replace an osu! session of the country and check whether the new one works.
+    ///
+    /// Returns the index of the replaced session or `None` if the country has no sessions.
+    pub async fn update_session(
+        &self,
+        session: String,
+        country: Option<&str>,
+    ) -> BotResult<Option<usize>> {
+        let Some(idx) = self.client().update_session(session, country).await else {
+            return Ok(None);
+        };
+
+        self.client().check_session(idx).await?;
+
+        Ok(Some(idx))
+    }
+}
@@ -17,6 +17,14 @@ use self::command::handle_command;
 mod command;
 
 pub async fn handle_interaction(ctx: Arc<Context>, interaction: Interaction) {
+    // Components and modals are handled by the
+    // paginations waiting for them through standby
+    if let Some(InteractionData::MessageComponent(_) | InteractionData::ModalSubmit(_)) =
+        interaction.data
+    {
+        return;
+    }
+
     let Some(cmd) = InteractionCommand::try_new(interaction) else {
         return error!("invalid interaction data");
     };
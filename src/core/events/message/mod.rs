@@ -6,8 +6,9 @@ use twilight_model::channel::Message;
 use crate::{
     core::{
         commands::prefix::{Args, PrefixCommand, Stream},
-        Context,
+        Context, CONFIG,
     },
+    util::ChannelExt,
     BotResult,
 };
 
@@ -64,6 +65,13 @@ async fn process_command(
 ) -> BotResult<()> {
     let channel = msg.channel_id;
 
+    if cmd.flags.only_owner() && CONFIG.get().unwrap().owner != Some(msg.author.id) {
+        let content = "That command can only be used by the bot owner";
+        msg.error(&ctx, content).await?;
+
+        return Ok(());
+    }
+
     // Prepare lightweight arguments
     let args = Args::new(&msg.content, stream);
 
@@ -1,9 +1,10 @@
 pub use self::{
-    config::{BotConfig, CONFIG},
+    config::{BotConfig, OsuSession, PpSource, ProxyConfig, CONFIG},
     context::Context,
     events::{event_loop, InteractionCommand},
 };
 
+mod cache;
 mod config;
 mod context;
 mod events;
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, sync::Arc, time::Duration};
 
 use http::StatusCode;
 use serde_json::Error;
@@ -11,6 +11,10 @@ pub enum CustomClientError {
     Http(#[from] hyper::http::Error),
     #[error("hyper error")]
     Hyper(#[from] hyper::Error),
+    #[error("received invalid .osu file from {url}")]
+    InvalidMapFile { url: String },
+    #[error("failed to retrieve .osu file of map {map_id} from any source")]
+    MapFileUnavailable { map_id: u32 },
     #[error("could not deserialize {kind}: {body}")]
     Parsing {
         body: String,
@@ -18,13 +22,68 @@ pub enum CustomClientError {
         #[source]
         source: Error,
     },
-    #[error("reached retry limit and still failed")]
-    RetryLimit,
+    #[error("too many pending requests")]
+    QueueFull,
+    #[error("the ratelimiter stopped so requests can't be sent")]
+    RatelimiterStopped,
+    #[error("no osu! session is usable anymore")]
+    SessionExpired,
+    #[error("shared request failed")]
+    Shared(#[source] Arc<CustomClientError>),
+    #[error("request to {url} timed out")]
+    Timeout { url: String },
     #[error("failed with status code {status} when requesting {url}")]
-    Status { status: StatusCode, url: String },
+    Status {
+        status: StatusCode,
+        url: String,
+        /// How long the server asked to wait before retrying
+        retry_after: Option<Duration>,
+    },
+    #[error("received a redirect or html page instead of json from {url}")]
+    UnexpectedPage { url: String },
+    #[error("the {0} is currently unreachable")]
+    Unreachable(&'static str),
 }
 
 impl CustomClientError {
+    /// Unwrap the error of a request that was shared between multiple callers
+    pub fn shared(err: Arc<Self>) -> Self {
+        match Arc::try_unwrap(err) {
+            Ok(err) => err,
+            // Keep the variant so callers can still react to it
+            Err(err) => {
+                let kept = match *err {
+                    Self::SessionExpired => Some(Self::SessionExpired),
+                    Self::Unreachable(site) => Some(Self::Unreachable(site)),
+                    Self::QueueFull => Some(Self::QueueFull),
+                    _ => None,
+                };
+
+                kept.unwrap_or_else(|| Self::Shared(err))
+            }
+        }
+    }
+
+    /// Whether the site failed to respond, i.e. a transport failure, a server error,
+    /// or requests being paused, as opposed to the request itself being the problem
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            Self::Hyper(_) | Self::Timeout { .. } | Self::Unreachable(_) => true,
+            Self::Status { status, .. } => status.is_server_error(),
+            Self::Shared(err) => err.is_unavailable(),
+            _ => false,
+        }
+    }
+
+    /// Whether osu! responded that the requested resource doesn't exist
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Self::Status { status, .. } => *status == StatusCode::NOT_FOUND,
+            Self::Shared(err) => err.is_not_found(),
+            _ => false,
+        }
+    }
+
     pub fn parsing(source: Error, bytes: &[u8], kind: ErrorKind) -> Self {
         Self::Parsing {
             body: String::from_utf8_lossy(bytes).into_owned(),
@@ -36,13 +95,23 @@ impl CustomClientError {
 
 #[derive(Debug)]
 pub enum ErrorKind {
+    Beatmap,
+    Beatmapset,
     Leaderboard,
+    Token,
+    User,
+    UserScore,
 }
 
 impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let kind = match self {
+            Self::Beatmap => "beatmap",
+            Self::Beatmapset => "beatmapset",
             Self::Leaderboard => "leaderboard",
+            Self::Token => "osu! API token",
+            Self::User => "user",
+            Self::UserScore => "user score",
         };
 
         f.write_str(kind)
@@ -0,0 +1,263 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use hashbrown::HashMap;
+use tokio::{
+    sync::{oneshot, Notify},
+    time::sleep,
+};
+use twilight_model::id::{
+    marker::{GuildMarker, UserMarker},
+    Id,
+};
+
+use super::{ClientResult, CustomClientError};
+
+/// Maximum amount of pending requests per requester
+const MAX_PENDING: usize = 64;
+
+/// Who a request is made for so that the ratelimit can be shared fairly
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Requester {
+    Guild(Id<GuildMarker>),
+    User(Id<UserMarker>),
+    /// Requests of the bot itself
+    Bot,
+}
+
+/// Ratelimits requests by granting them one at a time,
+/// going round-robin between requesters
+pub(super) struct FairQueue {
+    shared: Arc<Shared>,
+    interval: Duration,
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Pending requests of each requester in order of arrival
+    pending: HashMap<Requester, VecDeque<oneshot::Sender<()>>>,
+    /// Requesters with pending requests in the order they are served
+    order: VecDeque<Requester>,
+    /// Whether the dispatcher stopped so requests would never be granted
+    stopped: bool,
+}
+
+impl FairQueue {
+    /// Must be called within a tokio runtime
+    pub(super) fn new(interval: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState::default()),
+            notify: Notify::new(),
+        });
+
+        tokio::spawn(Self::dispatch(Arc::clone(&shared), interval));
+
+        Self { shared, interval }
+    }
+
+    /// Wait until the request is allowed to be sent
+    pub(super) async fn acquire(&self, requester: Requester) -> ClientResult<()> {
+        let rx = self.shared.enqueue(requester)?;
+        self.shared.notify.notify_one();
+
+        // The sender is only dropped if the dispatcher stopped
+        rx.await.map_err(|_| CustomClientError::RatelimiterStopped)
+    }
+
+    /// Amount of requests that would be granted before a new request of the requester
+    /// and how long it would take until it's granted
+    pub(super) fn wait_estimate(&self, requester: Requester) -> (usize, Duration) {
+        let state = self.shared.state.lock().unwrap();
+        let own = state.pending.get(&requester).map_or(0, VecDeque::len);
+
+        // Each other requester is served at most once per own pending request
+        let ahead = state
+            .pending
+            .iter()
+            .filter(|(other, _)| **other != requester)
+            .map(|(_, queue)| queue.len().min(own + 1))
+            .sum::<usize>()
+            + own;
+
+        (ahead, self.interval * ahead as u32)
+    }
+
+    async fn dispatch(shared: Arc<Shared>, interval: Duration) {
+        let _guard = DispatchGuard(Arc::clone(&shared));
+
+        loop {
+            let tx = loop {
+                match shared.next() {
+                    Some(tx) => break tx,
+                    None => shared.notify.notified().await,
+                }
+            };
+
+            // The request was cancelled in the meanwhile so its slot can be used right away
+            if tx.send(()).is_err() {
+                continue;
+            }
+
+            sleep(interval).await;
+        }
+    }
+}
+
+/// Fails all pending and future requests once the dispatcher stops, even through a panic
+struct DispatchGuard(Arc<Shared>);
+
+impl Drop for DispatchGuard {
+    fn drop(&mut self) {
+        warn!("Ratelimiter of an osu! session stopped");
+        self.0.stop();
+    }
+}
+
+impl Shared {
+    fn enqueue(&self, requester: Requester) -> ClientResult<oneshot::Receiver<()>> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.stopped {
+            return Err(CustomClientError::RatelimiterStopped);
+        }
+
+        let QueueState { pending, order, .. } = &mut *state;
+        let queue = pending.entry(requester).or_default();
+
+        if queue.len() >= MAX_PENDING {
+            return Err(CustomClientError::QueueFull);
+        }
+
+        let (tx, rx) = oneshot::channel();
+        queue.push_back(tx);
+
+        if queue.len() == 1 {
+            order.push_back(requester);
+        }
+
+        Ok(rx)
+    }
+
+    /// The next pending request in round-robin order
+    fn next(&self) -> Option<oneshot::Sender<()>> {
+        let mut state = self.state.lock().unwrap();
+        let QueueState { pending, order, .. } = &mut *state;
+
+        while let Some(requester) = order.pop_front() {
+            let Some(queue) = pending.get_mut(&requester) else {
+                continue;
+            };
+
+            let tx = queue.pop_front();
+
+            if queue.is_empty() {
+                pending.remove(&requester);
+            } else {
+                order.push_back(requester);
+            }
+
+            if let Some(tx) = tx.filter(|tx| !tx.is_closed()) {
+                return Some(tx);
+            }
+        }
+
+        None
+    }
+
+    /// Drop all pending requests so their receivers fail
+    fn stop(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.stopped = true;
+        state.pending.clear();
+        state.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shared() -> Shared {
+        Shared {
+            state: Mutex::new(QueueState::default()),
+            notify: Notify::new(),
+        }
+    }
+
+    #[test]
+    fn serves_requesters_round_robin() {
+        let shared = shared();
+
+        let spammer = Requester::Guild(Id::new(1));
+        let other = Requester::Guild(Id::new(2));
+
+        let mut spammed: Vec<_> = (0..3).map(|_| shared.enqueue(spammer).unwrap()).collect();
+        let mut others = shared.enqueue(other).unwrap();
+
+        // The first spammed request, then the other guild's, then the remaining spam
+        shared.next().unwrap().send(()).unwrap();
+        assert!(spammed[0].try_recv().is_ok());
+
+        shared.next().unwrap().send(()).unwrap();
+        assert!(others.try_recv().is_ok());
+        assert!(spammed[1].try_recv().is_err());
+
+        shared.next().unwrap().send(()).unwrap();
+        shared.next().unwrap().send(()).unwrap();
+        assert!(spammed[1].try_recv().is_ok());
+        assert!(spammed[2].try_recv().is_ok());
+
+        assert!(shared.next().is_none());
+    }
+
+    #[test]
+    fn caps_pending_requests() {
+        let shared = shared();
+        let requester = Requester::Bot;
+
+        let _pending: Vec<_> = (0..MAX_PENDING)
+            .map(|_| shared.enqueue(requester).unwrap())
+            .collect();
+
+        assert!(matches!(
+            shared.enqueue(requester),
+            Err(CustomClientError::QueueFull)
+        ));
+    }
+
+    #[test]
+    fn skips_cancelled_requests() {
+        let shared = shared();
+
+        let requester = Requester::User(Id::new(1));
+
+        drop(shared.enqueue(requester).unwrap());
+        let mut rx = shared.enqueue(requester).unwrap();
+
+        shared.next().unwrap().send(()).unwrap();
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn stopped_dispatcher_fails_requests() {
+        let shared = shared();
+        let requester = Requester::Bot;
+
+        let pending = shared.enqueue(requester).unwrap();
+        shared.stop();
+
+        assert!(pending.await.is_err());
+        assert!(matches!(
+            shared.enqueue(requester),
+            Err(CustomClientError::RatelimiterStopped)
+        ));
+    }
+}
@@ -1,10 +1,16 @@
-use std::{fmt::Write, hash::Hash};
+use std::{fmt::Write, hash::Hash, iter, path::PathBuf};
 
 use bytes::Bytes;
-use hashbrown::HashSet;
-use http::{header::COOKIE, request::Builder as RequestBuilder, Response, StatusCode};
+use eyre::Report;
+use futures::future;
+use hashbrown::{HashMap, HashSet};
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE},
+    request::Builder as RequestBuilder,
+    Response, StatusCode,
+};
 use hyper::{
-    client::{connect::dns::GaiResolver, Client as HyperClient, HttpConnector},
+    client::{Client as HyperClient, HttpConnector},
     header::USER_AGENT,
     Body, Method, Request,
 };
@@ -12,48 +18,116 @@ use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
 use leaky_bucket_lite::LeakyBucket;
 use rosu_v2::{
     model::score::Score,
-    prelude::{GameModIntermode, GameMode, GameModsIntermode},
+    mods,
+    prelude::{
+        BeatmapExtended, BeatmapUserScore, BeatmapsetExtended, GameMode, GameModsIntermode, UserId,
+    },
+};
+use serde::de::DeserializeOwned;
+use tokio::{
+    fs,
+    sync::Mutex,
+    time::{sleep, timeout, Duration},
 };
-use tokio::time::{sleep, Duration};
 
 use crate::{
-    core::BotConfig,
-    util::{constants::OSU_BASE, ExponentialBackoff},
+    core::{BotConfig, OsuSession},
+    util::mod_equivalence::ModEquivalence,
 };
 
-pub use self::error::*;
+pub use self::{
+    error::*,
+    fair_queue::Requester,
+    scores::{ExtendedLeaderboard, LeaderboardScope},
+};
 
-use self::scores::Scores;
+use self::{
+    osu_api::{ApiToken, ApiUser, TokenResponse, API_VERSION},
+    proxy::ProxyConnector,
+    retry::{CircuitBreaker, RetryAfter, RetryPolicy},
+    scores::Scores,
+    session_pool::{Session, SessionPool, SessionRoute},
+    single_flight::SingleFlight,
+};
 
 mod error;
+mod fair_queue;
+mod osu_api;
+mod proxy;
+mod retry;
 mod scores;
+mod session_pool;
+mod single_flight;
 
 type ClientResult<T> = Result<T, CustomClientError>;
 
 static MY_USER_AGENT: &str = env!("CARGO_PKG_NAME");
 
-#[derive(Copy, Clone, Eq, Hash, PartialEq)]
-#[repr(u8)]
+/// Maximum amount of scores on a leaderboard
+pub const LEADERBOARD_LIMIT: usize = 50;
+
+/// Index of the session whose account's friends make up friend leaderboards.
+///
+/// Friend leaderboards differ between accounts so they're always requested
+/// through the same session instead of rotating.
+pub const FRIEND_SESSION: usize = 0;
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 enum Site {
+    OsuApi,
     OsuHiddenApi,
     OsuMapFile,
+    /// Index of the configured mirror
+    MapMirror(usize),
 }
 
-type Client = HyperClient<HttpsConnector<HttpConnector<GaiResolver>>, Body>;
+impl Site {
+    fn name(self) -> &'static str {
+        match self {
+            Self::OsuApi => "osu! API",
+            Self::OsuHiddenApi | Self::OsuMapFile => "osu! website",
+            Self::MapMirror(_) => "map mirror",
+        }
+    }
+}
+
+type Client = HyperClient<HttpsConnector<ProxyConnector>, Body>;
+
+/// Map whose country leaderboard is requested to check the session
+const SESSION_CHECK_MAP: u32 = 2240404;
 
 pub struct CustomClient {
     client: Client,
-    osu_session: &'static str,
-    ratelimiters: [LeakyBucket; 2],
+    sessions: SessionPool,
+    /// The sessions as configured, identifying their persisted counterparts
+    configured_sessions: &'static [OsuSession],
+    session_path: Option<&'static PathBuf>,
+    ratelimiters: [LeakyBucket; 3],
+    breakers: [CircuitBreaker; 3],
+    /// Mirrors fail independently so each has its own breaker
+    mirror_breakers: Box<[CircuitBreaker]>,
+    single_flight: SingleFlight,
+    /// Locked while renewing so only one token is requested at a time
+    api_token: Mutex<Option<ApiToken>>,
+    osu_client_id: u64,
+    osu_client_secret: &'static str,
+    osu_base: &'static str,
+    map_mirrors: &'static [String],
+    read_timeout: Duration,
 }
 
 impl CustomClient {
     pub async fn new(config: &'static BotConfig) -> ClientResult<Self> {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        http.set_connect_timeout(Some(config.http.connect_timeout));
+
         let connector = HttpsConnectorBuilder::new()
             .with_webpki_roots()
             .https_or_http()
             .enable_http1()
-            .build();
+            .enable_http2()
+            .wrap_connector(ProxyConnector::new(http, &config.http.proxy));
 
         let client = HyperClient::builder().build(connector);
 
@@ -67,156 +141,541 @@ impl CustomClient {
         };
 
         let ratelimiters = [
-            ratelimiter(4),  // OsuHiddenApi
-            ratelimiter(20), // OsuMapFile
+            ratelimiter(20),  // OsuMapFile
+            ratelimiter(20),  // MapMirror
+            ratelimiter(100), // OsuApi
         ];
 
+        let session_path = config.paths.session.as_ref();
+        let cookies = load_sessions(&config.tokens.osu_sessions, session_path).await;
+
+        let sessions = config
+            .tokens
+            .osu_sessions
+            .iter()
+            .map(|session| session.country.as_deref())
+            .zip(cookies);
+
+        // The hidden api allows 4 requests per 10 seconds for each session,
+        // shared fairly between requesters
+        let sessions = SessionPool::new(sessions, Duration::from_millis(10_000 / 4));
+
         Ok(Self {
             client,
-            osu_session: &config.tokens.osu_session,
+            sessions,
+            configured_sessions: &config.tokens.osu_sessions,
+            session_path,
             ratelimiters,
+            breakers: Default::default(),
+            mirror_breakers: config
+                .urls
+                .map_mirrors
+                .iter()
+                .map(|_| CircuitBreaker::default())
+                .collect(),
+            single_flight: SingleFlight::default(),
+            api_token: Mutex::new(None),
+            osu_client_id: config.tokens.osu_client_id,
+            osu_client_secret: &config.tokens.osu_client_secret,
+            osu_base: &config.urls.osu_base,
+            map_mirrors: &config.urls.map_mirrors,
+            read_timeout: config.http.read_timeout,
         })
     }
 
-    async fn ratelimit(&self, site: Site) {
-        self.ratelimiters[site as usize].acquire_one().await
+    /// Replace the cookie of a session of the country, `None` meaning any country,
+    /// that is out of rotation, or of its first session if all of them are fine,
+    /// and put it back into rotation.
+    ///
+    /// Returns the index of the replaced session or `None` if the country has no sessions.
+    pub async fn update_session(&self, session: String, country: Option<&str>) -> Option<usize> {
+        let idx = self.sessions.replace(session, country)?;
+        self.persist_sessions().await;
+
+        Some(idx)
+    }
+
+    /// Persist the current cookie of all sessions, one per line,
+    /// each next to the configured session it belongs to
+    async fn persist_sessions(&self) {
+        let Some(path) = self.session_path else {
+            return;
+        };
+
+        let lines: Vec<_> = self
+            .configured_sessions
+            .iter()
+            .zip(self.sessions.sessions())
+            .map(|(configured, session)| persisted_line(configured, &session.cookie()))
+            .collect();
+
+        if let Err(err) = fs::write(path, lines.join("\n")).await {
+            let report = Report::new(err).wrap_err("failed to persist osu! sessions");
+            warn!("{report:?}");
+        }
+    }
+
+    /// Names of the sessions that expired since the last call, each only returned once
+    /// until it successfully authenticates again
+    pub fn unreported_expired_sessions(&self) -> Vec<String> {
+        self.sessions
+            .unreported_expiries()
+            .into_iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// How many sessions are in rotation
+    pub fn usable_sessions(&self) -> usize {
+        self.sessions.usable(SessionRoute::Any)
+    }
+
+    /// Make sure the session with the given index can read a country leaderboard
+    pub async fn check_session(&self, idx: usize) -> ClientResult<()> {
+        let url = leaderboard_url(
+            self.osu_base,
+            SESSION_CHECK_MAP,
+            None,
+            LeaderboardScope::Country,
+        );
+
+        let session = self.sessions.acquire_session(idx, Requester::Bot).await?;
+        let bytes = self.send_hidden_api_request(&url, session).await?;
+
+        serde_json::from_slice::<Scores>(&bytes)
+            .map_err(|e| CustomClientError::parsing(e, &bytes, ErrorKind::Leaderboard))?;
+
+        Ok(())
+    }
+
+    /// Check all sessions and return how many of them can read country leaderboards.
+    ///
+    /// Fails only if none of them can.
+    pub async fn check_sessions(&self) -> ClientResult<usize> {
+        let count = self.sessions.sessions().len();
+        let checks = (0..count).map(|idx| self.check_session(idx));
+
+        let mut usable = 0;
+        let mut errors = Vec::new();
+
+        for (idx, res) in future::join_all(checks).await.into_iter().enumerate() {
+            match res {
+                Ok(_) => usable += 1,
+                Err(err) => errors.push((idx, err)),
+            }
+        }
+
+        info!("osu! sessions: {}", self.sessions.health());
+
+        if usable == 0 {
+            if let Some((_, err)) = errors.pop() {
+                return Err(err);
+            }
+        }
+
+        for (idx, err) in errors {
+            // Expired sessions were already logged
+            if !matches!(err, CustomClientError::SessionExpired) {
+                let wrap = format!("failed to check osu! session #{}", idx + 1);
+                warn!("{:?}", Report::new(err).wrap_err(wrap));
+            }
+        }
+
+        Ok(usable)
+    }
+
+    /// How many hidden api requests would be sent through the sessions of the
+    /// leaderboard before one of the requester and how long it would take until it's sent
+    pub fn wait_estimate(
+        &self,
+        scope: LeaderboardScope,
+        country: Option<&str>,
+        requester: Requester,
+    ) -> (usize, Duration) {
+        self.sessions
+            .wait_estimate(session_route(scope, country), requester)
+    }
+
+    /// Concurrent requests of the same url share a single request.
+    ///
+    /// Hidden api requests are sent through a session of the given route.
+    async fn make_get_request(
+        &self,
+        url: &str,
+        site: Site,
+        route: SessionRoute<'_>,
+        requester: Requester,
+    ) -> ClientResult<Bytes> {
+        // The response depends on the route's sessions
+        let key = match route {
+            SessionRoute::Any => url.to_owned(),
+            SessionRoute::Country(_) | SessionRoute::Fixed(_) => format!("{route} {url}"),
+        };
+
+        self.single_flight
+            .run(&key, || self.send_get_request(url, site, route, requester))
+            .await
     }
 
-    async fn make_get_request(&self, url: &str, site: Site) -> ClientResult<Bytes> {
-        const ATTEMPTS: usize = 10;
+    /// Send the request, retrying transient failures according to the site's policy
+    async fn send_get_request(
+        &self,
+        url: &str,
+        site: Site,
+        route: SessionRoute<'_>,
+        requester: Requester,
+    ) -> ClientResult<Bytes> {
+        let breaker = match site {
+            Site::OsuHiddenApi => &self.breakers[0],
+            Site::OsuMapFile => &self.breakers[1],
+            Site::OsuApi => &self.breakers[2],
+            Site::MapMirror(idx) => &self.mirror_breakers[idx],
+        };
+
+        // Fail fast while the site is known to be unreachable
+        if !breaker.allows_request() {
+            return Err(CustomClientError::Unreachable(site.name()));
+        }
 
         trace!("GET request of url {url}");
 
-        let backoff = ExponentialBackoff::new(2).factor(500).max_delay(10_000);
-
-        for (duration, i) in backoff.take(ATTEMPTS).zip(1..) {
-            let req = self.make_get_request_(url, site).body(Body::empty())?;
-            self.ratelimit(site).await;
-            let response = self.client.request(req).await?;
-            let res = Self::error_for_status(response, url).await;
-
-            if let Err(CustomClientError::Status {
-                status: StatusCode::TOO_MANY_REQUESTS,
-                ..
-            }) = res
-            {
-                debug!("Retry attempt #{i} for {url} | Backoff {duration:?}");
-                sleep(duration).await;
-            } else {
+        let policy = RetryPolicy::new(site);
+        let mut backoff = policy.backoff();
+
+        let mut attempt = 1;
+
+        loop {
+            let res = self
+                .send_get_request_once(url, site, route, requester)
+                .await;
+
+            let err = match res {
+                // Another session might still be usable
+                Err(CustomClientError::SessionExpired) if self.sessions.usable(route) > 0 => {
+                    continue
+                }
+                Err(ref err) if retry::is_transient(err) => err,
+                // The request was not even sent
+                Err(CustomClientError::QueueFull) => return res,
+                _ => {
+                    // Any response, even an error, means the site is reachable
+                    breaker.record_success();
+
+                    return res;
+                }
+            };
+
+            if attempt >= policy.attempts {
+                if breaker.record_failure() {
+                    warn!(
+                        "The {} seems to be unreachable, pausing requests",
+                        site.name()
+                    );
+                }
+
                 return res;
             }
+
+            let delay = match policy.retry_after(err) {
+                Some(RetryAfter::Wait(delay)) => delay,
+                Some(RetryAfter::TooLong(delay)) => {
+                    // Retrying any sooner is pointless so fail fast until then
+                    breaker.hold_open(delay);
+                    warn!(
+                        "The {} asked to wait {delay:?} before retrying, pausing requests",
+                        site.name()
+                    );
+
+                    return res;
+                }
+                None => backoff.next().unwrap_or_default(),
+            };
+
+            debug!("Retry attempt #{attempt} for {url} | Backoff {delay:?}");
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send_get_request_once(
+        &self,
+        url: &str,
+        site: Site,
+        route: SessionRoute<'_>,
+        requester: Requester,
+    ) -> ClientResult<Bytes> {
+        let ratelimiter = match site {
+            Site::OsuHiddenApi => {
+                let session = self.sessions.acquire(route, requester).await?;
+
+                return self.send_hidden_api_request(url, session).await;
+            }
+            Site::OsuApi => return self.send_api_request(url).await,
+            Site::OsuMapFile => &self.ratelimiters[0],
+            Site::MapMirror(_) => &self.ratelimiters[1],
+        };
+
+        ratelimiter.acquire_one().await;
+        let req = self.make_get_request_(url).body(Body::empty())?;
+        let response = self.send(req, url).await?;
+
+        self.error_for_status(response, url).await
+    }
+
+    /// Send a hidden api request through the session,
+    /// taking it out of rotation if it failed to authenticate
+    async fn send_hidden_api_request(&self, url: &str, session: &Session) -> ClientResult<Bytes> {
+        let req = self
+            .make_get_request_(url)
+            .header(COOKIE, format!("osu_session={}", session.cookie()))
+            .body(Body::empty())?;
+
+        let res = self.send_hidden_api_request_(url, session, req).await;
+        session.record_request(res.is_ok());
+
+        if let Err(CustomClientError::SessionExpired) = res {
+            self.sessions.expire(session);
         }
 
-        Err(CustomClientError::RetryLimit)
+        res
     }
 
-    fn make_get_request_(&self, url: &str, site: Site) -> RequestBuilder {
+    async fn send_hidden_api_request_(
+        &self,
+        url: &str,
+        session: &Session,
+        req: Request<Body>,
+    ) -> ClientResult<Bytes> {
+        let response = self.send(req, url).await?;
+
+        // Without a valid session, osu! responds with a 401 or redirects to the login
+        if response.status() == StatusCode::UNAUTHORIZED || is_login_redirect(&response) {
+            return Err(CustomClientError::SessionExpired);
+        }
+
+        // Anything else that isn't json, e.g. a maintenance page, is not the session's fault
+        if response.status().is_redirection() {
+            return Err(CustomClientError::UnexpectedPage {
+                url: url.to_owned(),
+            });
+        }
+
+        // osu! rotates the session cookie so the latest one must be kept
+        if response.status().is_success() {
+            if let Some(cookie) = rotated_session(&response) {
+                trace!("Received rotated osu! session {session}");
+
+                if self.sessions.rotate(session, cookie) {
+                    self.persist_sessions().await;
+                }
+            }
+        }
+
+        let bytes = self.error_for_status(response, url).await?;
+
+        // e.g. a Cloudflare challenge
+        if is_html(&bytes) {
+            return Err(CustomClientError::UnexpectedPage {
+                url: url.to_owned(),
+            });
+        }
+
+        Ok(bytes)
+    }
+
+    /// Send an osu! API request, authorized through the client credentials
+    async fn send_api_request(&self, url: &str) -> ClientResult<Bytes> {
+        self.ratelimiters[2].acquire_one().await;
+
+        let token = self.api_token().await?;
+
+        let req = self
+            .make_get_request_(url)
+            .header(AUTHORIZATION, format!("Bearer {token}"))
+            .header("x-api-version", API_VERSION)
+            .body(Body::empty())?;
+
+        let response = self.send(req, url).await?;
+
+        // The token was revoked early so the next request needs a new one
+        if response.status() == StatusCode::UNAUTHORIZED {
+            self.api_token.lock().await.take();
+        }
+
+        self.error_for_status(response, url).await
+    }
+
+    /// The current osu! API token, requesting a new one if it's about to expire
+    async fn api_token(&self) -> ClientResult<String> {
+        let mut token = self.api_token.lock().await;
+
+        if let Some(access_token) = token.as_ref().and_then(ApiToken::access_token) {
+            return Ok(access_token.to_owned());
+        }
+
+        let url = format!("{}oauth/token", self.osu_base);
+
+        let body = serde_json::json!({
+            "client_id": self.osu_client_id,
+            "client_secret": self.osu_client_secret,
+            "grant_type": "client_credentials",
+            "scope": "public",
+        });
+
         let req = Request::builder()
+            .uri(&url)
+            .method(Method::POST)
+            .header(USER_AGENT, MY_USER_AGENT)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))?;
+
+        let response = self.send(req, &url).await?;
+        let bytes = self.error_for_status(response, &url).await?;
+
+        let response: TokenResponse = serde_json::from_slice(&bytes)
+            .map_err(|e| CustomClientError::parsing(e, &bytes, ErrorKind::Token))?;
+
+        let new_token = token.insert(ApiToken::from(response));
+
+        // Freshly requested so it can't be expired
+        Ok(new_token.access_token().unwrap_or_default().to_owned())
+    }
+
+    fn make_get_request_(&self, url: &str) -> RequestBuilder {
+        Request::builder()
             .uri(url)
             .method(Method::GET)
-            .header(USER_AGENT, MY_USER_AGENT);
+            .header(USER_AGENT, MY_USER_AGENT)
+    }
 
-        match site {
-            Site::OsuHiddenApi => req.header(COOKIE, format!("osu_session={}", self.osu_session)),
-            _ => req,
-        }
+    async fn send(&self, req: Request<Body>, url: &str) -> ClientResult<Response<Body>> {
+        let response = timeout(self.read_timeout, self.client.request(req))
+            .await
+            .map_err(|_| CustomClientError::Timeout {
+                url: url.to_owned(),
+            })??;
+
+        Ok(response)
     }
 
-    async fn error_for_status(response: Response<Body>, url: &str) -> ClientResult<Bytes> {
+    async fn error_for_status(&self, response: Response<Body>, url: &str) -> ClientResult<Bytes> {
         if response.status().is_client_error() || response.status().is_server_error() {
             Err(CustomClientError::Status {
                 status: response.status(),
                 url: url.to_owned(),
+                retry_after: retry::parse_retry_after(response.headers()),
             })
         } else {
-            let bytes = hyper::body::to_bytes(response.into_body()).await?;
+            let bytes = timeout(
+                self.read_timeout,
+                hyper::body::to_bytes(response.into_body()),
+            )
+            .await
+            .map_err(|_| CustomClientError::Timeout {
+                url: url.to_owned(),
+            })??;
 
             Ok(bytes)
         }
     }
 
-    // Retrieve the national leaderboard of a map
-    // If mods contain DT / NC, it will do another request for the opposite
-    // If mods dont contain Mirror and its a mania map, it will perform the
-    // same requests again but with Mirror enabled
-    pub async fn get_leaderboard(
+    /// Request the osu! API, deduplicating concurrent requests of the same url
+    async fn get_api<T: DeserializeOwned>(&self, url: &str, kind: ErrorKind) -> ClientResult<T> {
+        let bytes = self
+            .make_get_request(url, Site::OsuApi, SessionRoute::Any, Requester::Bot)
+            .await?;
+
+        serde_json::from_slice(&bytes).map_err(|e| CustomClientError::parsing(e, &bytes, kind))
+    }
+
+    pub async fn get_beatmap(&self, map_id: u32) -> ClientResult<BeatmapExtended> {
+        let url = format!("{}api/v2/beatmaps/{map_id}", self.osu_base);
+
+        self.get_api(&url, ErrorKind::Beatmap).await
+    }
+
+    pub async fn get_beatmapset(&self, mapset_id: u32) -> ClientResult<BeatmapsetExtended> {
+        let url = format!("{}api/v2/beatmapsets/{mapset_id}", self.osu_base);
+
+        self.get_api(&url, ErrorKind::Beatmapset).await
+    }
+
+    /// Retrieve the user's best score on the map, resolving usernames first
+    pub async fn get_beatmap_user_score(
         &self,
         map_id: u32,
-        mods: Option<&GameModsIntermode>,
+        user: &UserId,
         mode: GameMode,
-    ) -> ClientResult<Vec<Score>> {
-        let mut scores = self.get_leaderboard_(map_id, mods).await?;
+    ) -> ClientResult<BeatmapUserScore> {
+        let user_id = match user {
+            UserId::Id(user_id) => *user_id,
+            UserId::Name(name) => {
+                let url = format!(
+                    "{}api/v2/users/{}?key=username",
+                    self.osu_base,
+                    osu_api::encode_username(name.as_str()),
+                );
 
-        let non_mirror = mods
-            .map(|mods| !mods.contains(GameModIntermode::Mirror))
-            .unwrap_or(true);
-
-        // Check if another request for mania's MR is needed
-        if mode == GameMode::Mania && non_mirror {
-            let mods = match mods {
-                None => Some(rosu_v2::mods!(MR)),
-                Some(mods) => Some(mods.clone() | GameModIntermode::Mirror),
-            };
-
-            let mut new_scores = self.get_leaderboard_(map_id, mods.as_ref()).await?;
-            scores.append(&mut new_scores);
-            scores.sort_unstable_by(|a, b| b.score.cmp(&a.score));
-            let mut uniques = HashSet::with_capacity(50);
-            scores.retain(|s| uniques.insert(s.user_id));
-            scores.truncate(50);
-        }
+                let user: ApiUser = self.get_api(&url, ErrorKind::User).await?;
 
-        // Check if DT / NC is included
-        let mods = match mods {
-            Some(mods) if mods.contains(GameModIntermode::DoubleTime) => {
-                Some(mods.clone() | GameModIntermode::Nightcore)
+                user.id
             }
-            Some(mods) if mods.contains(GameModIntermode::Nightcore) => {
-                Some((mods.clone() - GameModIntermode::Nightcore) | GameModIntermode::DoubleTime)
-            }
-            Some(_) | None => None,
         };
 
-        // If DT / NC included, make another request
-        if mods.is_some() {
-            if mode == GameMode::Mania && non_mirror {
-                let mods = mods
-                    .as_ref()
-                    .map(|mods| mods.clone() | GameModIntermode::Mirror);
-                let mut new_scores = self.get_leaderboard_(map_id, mods.as_ref()).await?;
-                scores.append(&mut new_scores);
-            }
+        let url = format!(
+            "{}api/v2/beatmaps/{map_id}/scores/users/{user_id}?mode={}",
+            self.osu_base,
+            osu_api::api_mode(mode),
+        );
 
-            let mut new_scores = self.get_leaderboard_(map_id, mods.as_ref()).await?;
-            scores.append(&mut new_scores);
-            scores.sort_unstable_by(|a, b| b.score.cmp(&a.score));
-            let mut uniques = HashSet::with_capacity(50);
-            scores.retain(|s| uniques.insert(s.user_id));
-            scores.truncate(50);
-        }
+        self.get_api(&url, ErrorKind::UserScore).await
+    }
+
+    // Retrieve the leaderboard of a map for the given scope
+    // Every mod combination that is equivalent to the given mods is requested
+    // concurrently and the results are merged into a single leaderboard
+    // Country leaderboards are those of the given country, if any,
+    // and friend leaderboards those of the account of `FRIEND_SESSION`
+    pub async fn get_leaderboard(
+        &self,
+        map_id: u32,
+        mods: Option<&GameModsIntermode>,
+        mode: GameMode,
+        scope: LeaderboardScope,
+        country: Option<&str>,
+        requester: Requester,
+    ) -> ClientResult<Vec<Score>> {
+        let variants = ModEquivalence::new(mode).variants(mods);
+
+        let requests = variants
+            .iter()
+            .map(|mods| self.get_leaderboard_(map_id, mods.as_ref(), scope, country, requester));
+
+        let leaderboards = future::try_join_all(requests).await?;
+
+        let mut scores =
+            merge_leaderboards(leaderboards, |score| score.user_id, |score| score.score);
+        scores.truncate(LEADERBOARD_LIMIT);
 
         Ok(scores)
     }
 
-    // Retrieve the national leaderboard of a map
+    // Retrieve a single leaderboard page of a map
     async fn get_leaderboard_(
         &self,
         map_id: u32,
         mods: Option<&GameModsIntermode>,
+        scope: LeaderboardScope,
+        country: Option<&str>,
+        requester: Requester,
     ) -> ClientResult<Vec<Score>> {
-        let mut url = format!("{OSU_BASE}beatmaps/{map_id}/scores?type=country");
-
-        if let Some(mods) = mods {
-            if mods.is_empty() {
-                url.push_str("&mods[]=NM");
-            } else {
-                for m in mods.iter() {
-                    let _ = write!(url, "&mods[]={m}");
-                }
-            }
-        }
+        let url = leaderboard_url(self.osu_base, map_id, mods, scope);
 
-        let bytes = self.make_get_request(&url, Site::OsuHiddenApi).await?;
+        let route = session_route(scope, country);
+
+        let bytes = self
+            .make_get_request(&url, Site::OsuHiddenApi, route, requester)
+            .await?;
 
         let scores: Scores = serde_json::from_slice(&bytes)
             .map_err(|e| CustomClientError::parsing(e, &bytes, ErrorKind::Leaderboard))?;
@@ -224,15 +683,363 @@ impl CustomClient {
         Ok(scores.get())
     }
 
+    // Retrieve the .osu file of a map, falling back through the
+    // configured mirrors if the osu! website fails to provide it
     pub async fn get_map_file(&self, map_id: u32) -> ClientResult<Bytes> {
-        let url = format!("{OSU_BASE}osu/{map_id}");
-        let bytes = self.make_get_request(&url, Site::OsuMapFile).await?;
+        let mut last_err = None;
+
+        for (url, site) in map_file_sources(self.osu_base, self.map_mirrors, map_id) {
+            let res = self
+                .make_get_request(&url, site, SessionRoute::Any, Requester::Bot)
+                .await;
+
+            match res.map(|bytes| check_map_file(bytes, &url)) {
+                Ok(Ok(bytes)) => return Ok(bytes),
+                Ok(Err(err)) => {
+                    warn!("Received invalid .osu file from {url}");
+                    last_err = Some(err);
+                }
+                Err(err) => {
+                    let report = Report::new(err).wrap_err(format!("failed to request {url}"));
+                    warn!("{report:?}");
+                    last_err = Some(CustomClientError::MapFileUnavailable { map_id });
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or(CustomClientError::MapFileUnavailable { map_id }))
+    }
+}
+
+/// Sessions through which a leaderboard of the scope can be requested
+fn session_route(scope: LeaderboardScope, country: Option<&str>) -> SessionRoute<'_> {
+    match (scope, country) {
+        // Only country leaderboards depend on the session's country
+        (LeaderboardScope::Country, Some(country)) => SessionRoute::Country(country),
+        (LeaderboardScope::Country, None) | (LeaderboardScope::Global, _) => SessionRoute::Any,
+        (LeaderboardScope::Friend, _) => SessionRoute::Fixed(FRIEND_SESSION),
+    }
+}
+
+/// The configured sessions, each replaced by its persisted counterpart
+/// if there is one since that one is more recent.
+///
+/// Persisted cookies only count for the exact configured session they were
+/// persisted for so changing `OSU_SESSION` invalidates them.
+async fn load_sessions(configured: &[OsuSession], path: Option<&PathBuf>) -> Vec<String> {
+    let persisted = match path {
+        Some(path) => fs::read_to_string(path).await.unwrap_or_default(),
+        None => String::new(),
+    };
+
+    let persisted = parse_persisted(&persisted);
+
+    configured
+        .iter()
+        .enumerate()
+        .map(|(idx, session)| {
+            let key = (session.country.as_deref(), session.cookie.as_str());
+
+            match persisted.get(&key) {
+                Some(cookie) => {
+                    info!("osu! session #{}: using the persisted cookie", idx + 1);
+
+                    (*cookie).to_owned()
+                }
+                None => {
+                    info!("osu! session #{}: using the configured cookie", idx + 1);
+
+                    session.cookie.clone()
+                }
+            }
+        })
+        .collect()
+}
+
+/// Persisted line of the form `<country or -> <configured cookie> <current cookie>`
+fn persisted_line(configured: &OsuSession, cookie: &str) -> String {
+    let country = configured.country.as_deref().unwrap_or("-");
 
-        // On invalid response, retry once more
-        if bytes.starts_with(b"<html>") {
-            self.make_get_request(&url, Site::OsuMapFile).await
+    format!("{country} {} {cookie}", configured.cookie)
+}
+
+/// Current cookies of persisted lines, keyed by country and configured cookie
+fn parse_persisted(content: &str) -> HashMap<(Option<&str>, &str), &str> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut split = line.split_whitespace();
+            let country = split.next()?;
+            let configured = split.next()?;
+            let current = split.next()?;
+
+            if split.next().is_some() {
+                return None;
+            }
+
+            let country = (country != "-").then_some(country);
+
+            Some(((country, configured), current))
+        })
+        .collect()
+}
+
+fn leaderboard_url(
+    osu_base: &str,
+    map_id: u32,
+    mods: Option<&GameModsIntermode>,
+    scope: LeaderboardScope,
+) -> String {
+    let mut url = format!("{osu_base}beatmaps/{map_id}/scores?type={}", scope.as_str());
+
+    if let Some(mods) = mods {
+        if mods.is_empty() {
+            url.push_str("&mods[]=NM");
         } else {
-            Ok(bytes)
+            for m in mods.iter() {
+                let _ = write!(url, "&mods[]={m}");
+            }
         }
     }
+
+    url
+}
+
+/// Urls to request a map's .osu file from in order:
+/// the osu! website first, then the mirrors as configured
+fn map_file_sources<'m>(
+    osu_base: &str,
+    mirrors: &'m [String],
+    map_id: u32,
+) -> impl Iterator<Item = (String, Site)> + 'm {
+    let official = format!("{osu_base}osu/{map_id}");
+
+    let mirrors = mirrors.iter().enumerate().map(move |(idx, mirror)| {
+        let url = if mirror.contains("{map_id}") {
+            mirror.replace("{map_id}", &map_id.to_string())
+        } else {
+            format!("{mirror}{map_id}")
+        };
+
+        (url, Site::MapMirror(idx))
+    });
+
+    iter::once((official, Site::OsuMapFile)).chain(mirrors)
+}
+
+/// Only pass on content that is actually a .osu file
+fn check_map_file(bytes: Bytes, url: &str) -> ClientResult<Bytes> {
+    if is_map_file(&bytes) {
+        Ok(bytes)
+    } else {
+        Err(CustomClientError::InvalidMapFile {
+            url: url.to_owned(),
+        })
+    }
+}
+
+/// Whether the content looks like a .osu file rather than e.g. an error page
+fn is_map_file(bytes: &[u8]) -> bool {
+    const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+    let bytes = bytes.strip_prefix(BOM).unwrap_or(bytes);
+
+    let trimmed = bytes
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .map_or(&bytes[..0], |idx| &bytes[idx..]);
+
+    trimmed.starts_with(b"osu file format")
+}
+
+/// Whether the response redirects to the login, i.e. the session is not logged in
+fn is_login_redirect(response: &Response<Body>) -> bool {
+    response.status().is_redirection()
+        && response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .map_or(false, is_login_location)
+}
+
+fn is_login_location(location: &str) -> bool {
+    let path = location.split(['?', '#']).next().unwrap_or_default();
+
+    path.ends_with("/login") || path.ends_with("/session")
+}
+
+fn is_html(bytes: &Bytes) -> bool {
+    let trimmed = bytes
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .map_or(&bytes[..0], |idx| &bytes[idx..]);
+
+    trimmed.starts_with(b"<")
+}
+
+/// The `osu_session` cookie of the response's `Set-Cookie` headers
+fn rotated_session(response: &Response<Body>) -> Option<String> {
+    response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .filter_map(|cookie| cookie.split(';').next())
+        .find_map(|cookie| cookie.trim().strip_prefix("osu_session="))
+        .filter(|session| !session.is_empty())
+        .map(str::to_owned)
+}
+
+/// Mod combinations whose leaderboards extend the regular leaderboard
+pub fn extended_mods() -> [GameModsIntermode; 10] {
+    [
+        GameModsIntermode::new(),
+        mods!(HD),
+        mods!(HR),
+        mods!(DT),
+        mods!(HD HR),
+        mods!(HD DT),
+        mods!(EZ),
+        mods!(HD EZ),
+        mods!(HT),
+        mods!(FL),
+    ]
+}
+
+/// Merge multiple leaderboards into one, keeping only the best score of each user
+/// and sorting by score. Ties are won by the leaderboard that came first.
+pub fn merge_leaderboards<T>(
+    leaderboards: Vec<Vec<T>>,
+    user_id: impl Fn(&T) -> u32,
+    score: impl Fn(&T) -> u32,
+) -> Vec<T> {
+    let mut scores: Vec<_> = leaderboards.into_iter().flatten().collect();
+    scores.sort_by(|a, b| score(b).cmp(&score(a)));
+
+    let mut uniques = HashSet::with_capacity(scores.len());
+    scores.retain(|s| uniques.insert(user_id(s)));
+
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(leaderboards: Vec<Vec<(u32, u32, &str)>>) -> Vec<(u32, u32, &str)> {
+        merge_leaderboards(leaderboards, |s| s.0, |s| s.1)
+    }
+
+    #[test]
+    fn merge_keeps_best_score_per_user() {
+        let dt = vec![(1, 900, "DT"), (2, 500, "DT")];
+        let nc = vec![(2, 800, "NC"), (1, 700, "NC"), (3, 600, "NC")];
+
+        let merged = merge(vec![dt, nc]);
+
+        assert_eq!(merged, vec![(1, 900, "DT"), (2, 800, "NC"), (3, 600, "NC")]);
+    }
+
+    #[test]
+    fn merge_prefers_first_leaderboard_on_ties() {
+        let dt = vec![(1, 900, "DT")];
+        let nc = vec![(1, 900, "NC")];
+
+        assert_eq!(merge(vec![dt, nc]), vec![(1, 900, "DT")]);
+    }
+
+    #[test]
+    fn only_login_redirects_expire_sessions() {
+        assert!(is_login_location("https://osu.ppy.sh/session"));
+        assert!(is_login_location("/login?redirect=%2Fbeatmaps"));
+        assert!(!is_login_location("https://osu.ppy.sh/maintenance"));
+        assert!(!is_login_location("https://osu.ppy.sh/beatmaps/2240404"));
+    }
+
+    #[test]
+    fn persisted_cookies_belong_to_configured_sessions() {
+        let session = |country: Option<&str>, cookie: &str| OsuSession {
+            country: country.map(str::to_owned),
+            cookie: cookie.to_owned(),
+        };
+
+        let content = [
+            persisted_line(&session(Some("DE"), "de"), "de-rotated"),
+            persisted_line(&session(None, "any"), "any-rotated"),
+            // Lines of the previous format are ignored
+            "old-cookie".to_owned(),
+        ]
+        .join("\n");
+
+        let persisted = parse_persisted(&content);
+
+        assert_eq!(persisted.len(), 2);
+        assert_eq!(persisted[&(Some("DE"), "de")], "de-rotated");
+        assert_eq!(persisted[&(None, "any")], "any-rotated");
+
+        // A changed country or cookie no longer matches
+        assert!(!persisted.contains_key(&(Some("US"), "de")));
+        assert!(!persisted.contains_key(&(Some("DE"), "de-new")));
+    }
+
+    #[test]
+    fn map_files_are_recognized() {
+        assert!(is_map_file(b"osu file format v14\r\n\r\n[General]"));
+        assert!(is_map_file(b"\xEF\xBB\xBF\r\n  osu file format v128"));
+        assert!(!is_map_file(b""));
+        assert!(!is_map_file(b"<!DOCTYPE html><html>Not found</html>"));
+        assert!(!is_map_file(b"{\"error\":\"not found\"}"));
+    }
+
+    #[test]
+    fn invalid_map_files_are_rejected() {
+        let url = "https://mirror.example/osu/1";
+
+        let valid = Bytes::from_static(b"osu file format v14");
+        assert_eq!(check_map_file(valid.clone(), url).unwrap(), valid);
+
+        let invalid = Bytes::from_static(b"<html>Cloudflare</html>");
+
+        match check_map_file(invalid, url) {
+            Err(CustomClientError::InvalidMapFile { url: err_url }) => assert_eq!(err_url, url),
+            other => panic!("expected an invalid map file error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn map_files_fall_back_to_mirrors_in_order() {
+        let mirrors = [
+            "https://first.example/osu/".to_owned(),
+            "https://second.example/d/{map_id}.osu".to_owned(),
+        ];
+
+        let sources: Vec<_> = map_file_sources("https://osu.ppy.sh/", &mirrors, 42).collect();
+
+        let expected = vec![
+            ("https://osu.ppy.sh/osu/42".to_owned(), Site::OsuMapFile),
+            (
+                "https://first.example/osu/42".to_owned(),
+                Site::MapMirror(0),
+            ),
+            (
+                "https://second.example/d/42.osu".to_owned(),
+                Site::MapMirror(1),
+            ),
+        ];
+
+        assert_eq!(sources, expected);
+        assert_eq!(map_file_sources("https://osu.ppy.sh/", &[], 42).count(), 1);
+    }
+
+    #[test]
+    fn merge_is_not_limited() {
+        let first = (0..40).map(|i| (i, 1000 + i, "NM")).collect();
+        let second = (40..80).map(|i| (i, 1000 + i, "HD")).collect();
+
+        let merged = merge(vec![first, second]);
+
+        assert_eq!(merged.len(), 80);
+        assert_eq!(merged[0], (79, 1079, "HD"));
+        assert_eq!(merged[79], (0, 1000, "NM"));
+    }
 }
@@ -0,0 +1,80 @@
+use std::fmt::Write;
+
+use rosu_v2::prelude::GameMode;
+use serde::Deserialize;
+use tokio::time::{Duration, Instant};
+
+/// Version whose response format the rosu-v2 models expect
+pub const API_VERSION: &str = "20220705";
+
+/// Tokens are renewed this long before they expire so requests don't race the expiry
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Client credentials token of the osu! API
+pub struct ApiToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+impl ApiToken {
+    pub fn access_token(&self) -> Option<&str> {
+        (Instant::now() + EXPIRY_MARGIN < self.expires_at).then_some(self.access_token.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TokenResponse {
+    access_token: String,
+    /// Seconds until the token expires
+    expires_in: u64,
+}
+
+impl From<TokenResponse> for ApiToken {
+    fn from(response: TokenResponse) -> Self {
+        Self {
+            access_token: response.access_token,
+            expires_at: Instant::now() + Duration::from_secs(response.expires_in),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ApiUser {
+    pub id: u32,
+}
+
+/// Value of the `mode` query parameter
+pub fn api_mode(mode: GameMode) -> &'static str {
+    match mode {
+        GameMode::Osu => "osu",
+        GameMode::Taiko => "taiko",
+        GameMode::Catch => "fruits",
+        GameMode::Mania => "mania",
+    }
+}
+
+/// Percent-encode a username so it can be used as path segment
+pub fn encode_username(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usernames_are_encoded() {
+        assert_eq!(encode_username("Badewanne3"), "Badewanne3");
+        assert_eq!(encode_username("[Mr Name]"), "%5BMr%20Name%5D");
+    }
+}
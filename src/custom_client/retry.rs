@@ -0,0 +1,275 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use http::{header::RETRY_AFTER, HeaderMap, StatusCode};
+
+use crate::util::ExponentialBackoff;
+
+use super::{CustomClientError, Site};
+
+/// How requests to a site are retried
+#[derive(Copy, Clone)]
+pub(super) struct RetryPolicy {
+    /// Maximum amount of attempts, including the first one
+    pub(super) attempts: usize,
+    /// Factor in milliseconds of the exponential backoff
+    factor: u32,
+    /// Upper bound in milliseconds of any delay.
+    /// A longer `Retry-After` is not waited for but fails the request.
+    max_delay: u64,
+}
+
+impl RetryPolicy {
+    pub(super) fn new(site: Site) -> Self {
+        match site {
+            // 1s - 2s - 4s - 8s
+            Site::OsuHiddenApi => Self {
+                attempts: 5,
+                factor: 500,
+                max_delay: 10_000,
+            },
+            // 500ms - 1s
+            Site::OsuApi | Site::OsuMapFile => Self {
+                attempts: 3,
+                factor: 250,
+                max_delay: 5000,
+            },
+            // Mirrors are only fallbacks so don't wait for them too long
+            Site::MapMirror(_) => Self {
+                attempts: 2,
+                factor: 250,
+                max_delay: 2000,
+            },
+        }
+    }
+
+    /// Delays between attempts, with jitter so that retries don't all happen at once
+    pub(super) fn backoff(self) -> impl Iterator<Item = Duration> {
+        ExponentialBackoff::new(2)
+            .factor(self.factor)
+            .max_delay(self.max_delay)
+            .map(jitter)
+    }
+
+    /// The delay requested by the server, if any
+    pub(super) fn retry_after(self, err: &CustomClientError) -> Option<RetryAfter> {
+        let CustomClientError::Status {
+            retry_after: Some(retry_after),
+            ..
+        } = err
+        else {
+            return None;
+        };
+
+        if *retry_after <= Duration::from_millis(self.max_delay) {
+            Some(RetryAfter::Wait(*retry_after))
+        } else {
+            Some(RetryAfter::TooLong(*retry_after))
+        }
+    }
+}
+
+/// A `Retry-After` delay compared to the policy's budget
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum RetryAfter {
+    /// The delay to wait before retrying
+    Wait(Duration),
+    /// The delay exceeds the policy so the request should fail instead
+    TooLong(Duration),
+}
+
+/// Whether the error is likely temporary so the request can be retried
+pub(super) fn is_transient(err: &CustomClientError) -> bool {
+    match err {
+        CustomClientError::Hyper(err) => !err.is_user(),
+        // Temporary pages like a Cloudflare challenge or maintenance
+        CustomClientError::Timeout { .. } | CustomClientError::UnexpectedPage { .. } => true,
+        CustomClientError::Status { status, .. } => {
+            *status == StatusCode::TOO_MANY_REQUESTS
+                || (status.is_server_error() && *status != StatusCode::NOT_IMPLEMENTED)
+        }
+        _ => false,
+    }
+}
+
+/// Parse the `Retry-After` header if it specifies an amount of seconds
+pub(super) fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Randomize the duration to somewhere between its half and its full value
+fn jitter(duration: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    let half = duration / 2;
+
+    half + half.mul_f64((random % 1000) as f64 / 1000.0)
+}
+
+/// Amount of consecutive failures after which a site is considered unreachable
+const FAILURE_THRESHOLD: usize = 5;
+
+/// How long requests fail right away once a site is considered unreachable
+const COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Stops requests to a site for a while after it failed repeatedly
+#[derive(Default)]
+pub(super) struct CircuitBreaker {
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: usize,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Whether requests are currently allowed.
+    ///
+    /// Once the cooldown is over, requests are allowed again
+    /// but a single failure opens the breaker anew.
+    pub(super) fn allows_request(&self) -> bool {
+        let state = self.state.lock().unwrap();
+
+        state
+            .open_until
+            .map_or(true, |open_until| open_until <= Instant::now())
+    }
+
+    pub(super) fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = 0;
+        state.open_until = None;
+    }
+
+    /// Keep the breaker open for at least the given duration,
+    /// e.g. because the site asked not to send requests until then
+    pub(super) fn hold_open(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let deadline = Instant::now() + duration;
+
+        if state
+            .open_until
+            .map_or(true, |open_until| open_until < deadline)
+        {
+            state.open_until = Some(deadline);
+        }
+    }
+
+    /// Returns `true` if the failure opened the breaker
+    pub(super) fn record_failure(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+
+        if state.failures < FAILURE_THRESHOLD {
+            return false;
+        }
+
+        state.open_until = Some(Instant::now() + COOLDOWN);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn breaker_opens_after_threshold() {
+        let breaker = CircuitBreaker::default();
+
+        for _ in 1..FAILURE_THRESHOLD {
+            assert!(!breaker.record_failure());
+            assert!(breaker.allows_request());
+        }
+
+        assert!(breaker.record_failure());
+        assert!(!breaker.allows_request());
+
+        breaker.record_success();
+        assert!(breaker.allows_request());
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let duration = Duration::from_millis(1000);
+
+        for _ in 0..100 {
+            let jittered = jitter(duration);
+            assert!(jittered >= duration / 2 && jittered <= duration);
+        }
+    }
+
+    #[test]
+    fn breaker_held_open_until_deadline() {
+        let breaker = CircuitBreaker::default();
+
+        breaker.hold_open(Duration::from_millis(50));
+        assert!(!breaker.allows_request());
+
+        // A shorter hold does not cut the current one short
+        breaker.hold_open(Duration::ZERO);
+        assert!(!breaker.allows_request());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.allows_request());
+    }
+
+    #[test]
+    fn retry_after_beyond_budget_is_too_long() {
+        let err = |secs| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(secs).unwrap());
+
+            CustomClientError::Status {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                url: String::new(),
+                retry_after: parse_retry_after(&headers),
+            }
+        };
+
+        let policy = RetryPolicy::new(Site::OsuHiddenApi);
+
+        assert_eq!(
+            policy.retry_after(&err("5")),
+            Some(RetryAfter::Wait(Duration::from_secs(5)))
+        );
+
+        assert_eq!(
+            policy.retry_after(&err("120")),
+            Some(RetryAfter::TooLong(Duration::from_secs(120)))
+        );
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        let status = |status| CustomClientError::Status {
+            status,
+            url: String::new(),
+            retry_after: None,
+        };
+
+        assert!(is_transient(&status(StatusCode::TOO_MANY_REQUESTS)));
+        assert!(is_transient(&status(StatusCode::BAD_GATEWAY)));
+        assert!(is_transient(&status(StatusCode::SERVICE_UNAVAILABLE)));
+        assert!(!is_transient(&status(StatusCode::NOT_FOUND)));
+        assert!(!is_transient(&CustomClientError::SessionExpired));
+        assert!(is_transient(&CustomClientError::UnexpectedPage {
+            url: String::new()
+        }));
+    }
+}
@@ -1,5 +1,5 @@
 use rosu_v2::model::score::Score;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
 pub struct Scores {
@@ -11,3 +11,40 @@ impl Scores {
         self.scores
     }
 }
+
+/// A leaderboard that was extended beyond its regular limit by merging
+/// multiple mod-filtered leaderboards
+pub struct ExtendedLeaderboard {
+    pub scores: Vec<Score>,
+    /// Scores with a lower value might be preceded by scores that
+    /// weren't retrieved so their rank is uncertain
+    pub uncertain_below: Option<u32>,
+}
+
+/// Which players' scores a leaderboard should contain
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum LeaderboardScope {
+    #[default]
+    Country,
+    Global,
+    Friend,
+}
+
+impl LeaderboardScope {
+    /// Value of the `type` query parameter
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Country => "country",
+            Self::Global => "global",
+            Self::Friend => "friend",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Country => "National",
+            Self::Global => "Global",
+            Self::Friend => "Friend",
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
+
+use super::{
+    fair_queue::{FairQueue, Requester},
+    ClientResult, CustomClientError,
+};
+
+/// A supporter session with its own ratelimit
+pub(super) struct Session {
+    idx: usize,
+    /// Country of the session's account
+    country: Option<&'static str>,
+    cookie: RwLock<String>,
+    queue: FairQueue,
+    /// Whether the session failed to authenticate and was taken out of rotation
+    expired: AtomicBool,
+    /// Whether the owner was told about the session's expiry
+    reported: AtomicBool,
+    requests: AtomicUsize,
+    failures: AtomicUsize,
+}
+
+impl Session {
+    fn new(idx: usize, country: Option<&'static str>, cookie: String, interval: Duration) -> Self {
+        Self {
+            idx,
+            country,
+            cookie: RwLock::new(cookie),
+            queue: FairQueue::new(interval),
+            expired: AtomicBool::new(false),
+            reported: AtomicBool::new(false),
+            requests: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    pub(super) fn cookie(&self) -> String {
+        self.cookie.read().unwrap().clone()
+    }
+
+    pub(super) fn is_expired(&self) -> bool {
+        self.expired.load(Ordering::SeqCst)
+    }
+
+    /// Whether the session belongs to the country, `None` matching any session
+    fn is_of(&self, country: Option<&str>) -> bool {
+        country.map_or(true, |country| self.country == Some(country))
+    }
+
+    /// Whether the session may send requests of the route
+    fn serves(&self, route: SessionRoute<'_>) -> bool {
+        match route {
+            SessionRoute::Any => true,
+            SessionRoute::Country(country) => self.country == Some(country),
+            SessionRoute::Fixed(idx) => self.idx == idx,
+        }
+    }
+
+    pub(super) fn record_request(&self, success: bool) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+
+        if success {
+            // A later expiry must be reported again
+            self.reported.store(false, Ordering::SeqCst);
+        } else {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Display for Session {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "#{}", self.idx + 1)?;
+
+        if let Some(country) = self.country {
+            write!(f, " ({country})")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Which sessions may send a hidden api request
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) enum SessionRoute<'c> {
+    /// The response is the same for all sessions
+    Any,
+    /// The response depends on the session's country e.g. national leaderboards
+    Country(&'c str),
+    /// The response depends on the session's account e.g. friend leaderboards
+    Fixed(usize),
+}
+
+impl Display for SessionRoute<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Any => f.write_str("any"),
+            Self::Country(country) => f.write_str(country),
+            Self::Fixed(idx) => write!(f, "#{}", idx + 1),
+        }
+    }
+}
+
+/// Supporter sessions taking turns in sending hidden api requests
+pub(super) struct SessionPool {
+    sessions: Box<[Session]>,
+    next: AtomicUsize,
+    interval: Duration,
+}
+
+impl SessionPool {
+    /// Must be called within a tokio runtime
+    pub(super) fn new(
+        sessions: impl IntoIterator<Item = (Option<&'static str>, String)>,
+        interval: Duration,
+    ) -> Self {
+        let sessions = sessions
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (country, cookie))| Session::new(idx, country, cookie, interval))
+            .collect();
+
+        Self {
+            sessions,
+            next: AtomicUsize::new(0),
+            interval,
+        }
+    }
+
+    pub(super) fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    /// Wait until the route's next session in rotation may send a request
+    pub(super) async fn acquire(
+        &self,
+        route: SessionRoute<'_>,
+        requester: Requester,
+    ) -> ClientResult<&Session> {
+        let session = self
+            .next_usable(route)
+            .ok_or(CustomClientError::SessionExpired)?;
+        session.queue.acquire(requester).await?;
+
+        Ok(session)
+    }
+
+    /// Wait until the given session may send a request, regardless of its rotation
+    pub(super) async fn acquire_session(
+        &self,
+        idx: usize,
+        requester: Requester,
+    ) -> ClientResult<&Session> {
+        let session = &self.sessions[idx];
+        session.queue.acquire(requester).await?;
+
+        Ok(session)
+    }
+
+    fn next_usable(&self, route: SessionRoute<'_>) -> Option<&Session> {
+        let len = self.sessions.len();
+
+        (0..len)
+            .map(|_| &self.sessions[self.next.fetch_add(1, Ordering::Relaxed) % len])
+            .find(|session| session.serves(route) && !session.is_expired())
+    }
+
+    /// How many of the route's sessions are in rotation
+    pub(super) fn usable(&self, route: SessionRoute<'_>) -> usize {
+        self.sessions
+            .iter()
+            .filter(|session| session.serves(route) && !session.is_expired())
+            .count()
+    }
+
+    /// Take the session out of rotation.
+    ///
+    /// Returns `true` if it was in rotation until now.
+    pub(super) fn expire(&self, session: &Session) -> bool {
+        if session.expired.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        warn!(
+            "osu! session {session} failed to authenticate, taking it out of rotation \
+            ({}/{} left) | {}",
+            self.usable(SessionRoute::Any),
+            self.sessions.len(),
+            self.health()
+        );
+
+        true
+    }
+
+    /// Sessions that were taken out of rotation since the last call
+    /// and that the owner wasn't told about yet
+    pub(super) fn unreported_expiries(&self) -> Vec<&Session> {
+        self.sessions
+            .iter()
+            .filter(|session| session.is_expired())
+            .filter(|session| !session.reported.swap(true, Ordering::SeqCst))
+            .collect()
+    }
+
+    /// Replace the cookie of the country's first session that is out of rotation,
+    /// or of its first session if all of them are in rotation,
+    /// and put it back into rotation.
+    ///
+    /// Returns the index of the replaced session or `None` if the country has no sessions.
+    pub(super) fn replace(&self, cookie: String, country: Option<&str>) -> Option<usize> {
+        let mut sessions = self
+            .sessions
+            .iter()
+            .filter(|session| session.is_of(country));
+        let first = sessions.clone().next()?;
+
+        let session = sessions
+            .find(|session| session.is_expired())
+            .unwrap_or(first);
+
+        *session.cookie.write().unwrap() = cookie;
+        session.expired.store(false, Ordering::SeqCst);
+        info!("Replaced osu! session {session}");
+
+        Some(session.idx)
+    }
+
+    /// Keep the cookie that osu! rotated the session to.
+    ///
+    /// Returns `true` if the cookie changed.
+    pub(super) fn rotate(&self, session: &Session, cookie: String) -> bool {
+        let mut current = session.cookie.write().unwrap();
+
+        if *current == cookie {
+            return false;
+        }
+
+        *current = cookie;
+
+        true
+    }
+
+    /// How many requests would be sent through the route's sessions before one
+    /// of the requester and roughly how long it would take until it's sent
+    pub(super) fn wait_estimate(
+        &self,
+        route: SessionRoute<'_>,
+        requester: Requester,
+    ) -> (usize, Duration) {
+        let usable = self
+            .sessions
+            .iter()
+            .filter(|session| session.serves(route) && !session.is_expired())
+            .map(|session| session.queue.wait_estimate(requester).0);
+
+        let (count, ahead) = usable.fold((0, 0), |(count, sum), ahead| (count + 1, sum + ahead));
+
+        // Requests are spread across all sessions so they're processed in parallel
+        let wait = self.interval * ahead as u32 / count.max(1);
+
+        (ahead, wait)
+    }
+
+    pub(super) fn health(&self) -> PoolHealth<'_> {
+        PoolHealth(self)
+    }
+}
+
+/// Summary of each session's state for logging
+pub(super) struct PoolHealth<'p>(&'p SessionPool);
+
+impl Display for PoolHealth<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for (i, session) in self.0.sessions.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+
+            let state = if session.is_expired() {
+                "out of rotation"
+            } else {
+                "healthy"
+            };
+
+            write!(
+                f,
+                "{session} {state} ({} requests, {} failed)",
+                session.requests.load(Ordering::Relaxed),
+                session.failures.load(Ordering::Relaxed),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(len: usize) -> SessionPool {
+        let sessions = (0..len).map(|i| (None, format!("session{i}")));
+
+        SessionPool::new(sessions, Duration::from_millis(100))
+    }
+
+    #[tokio::test]
+    async fn rotates_through_sessions() {
+        let pool = pool(3);
+
+        let cookies: Vec<_> = (0..4)
+            .map(|_| pool.next_usable(SessionRoute::Any).unwrap().cookie())
+            .collect();
+
+        assert_eq!(cookies, ["session0", "session1", "session2", "session0"]);
+    }
+
+    #[tokio::test]
+    async fn skips_expired_sessions() {
+        let pool = pool(3);
+
+        assert!(pool.expire(&pool.sessions[1]));
+        assert!(!pool.expire(&pool.sessions[1]));
+        assert_eq!(pool.usable(SessionRoute::Any), 2);
+
+        let cookies: Vec<_> = (0..4)
+            .map(|_| pool.next_usable(SessionRoute::Any).unwrap().cookie())
+            .collect();
+
+        assert_eq!(cookies, ["session0", "session2", "session0", "session2"]);
+    }
+
+    #[tokio::test]
+    async fn replacing_puts_session_back_into_rotation() {
+        let pool = pool(2);
+
+        pool.expire(&pool.sessions[0]);
+        pool.expire(&pool.sessions[1]);
+        assert!(pool.next_usable(SessionRoute::Any).is_none());
+
+        assert_eq!(pool.replace("new".to_owned(), None), Some(0));
+        assert_eq!(pool.next_usable(SessionRoute::Any).unwrap().cookie(), "new");
+
+        assert_eq!(pool.replace("newer".to_owned(), None), Some(1));
+        assert_eq!(pool.usable(SessionRoute::Any), 2);
+
+        // Without expired sessions, the first one is replaced
+        assert_eq!(pool.replace("newest".to_owned(), None), Some(0));
+        assert_eq!(pool.sessions[0].cookie(), "newest");
+    }
+
+    #[tokio::test]
+    async fn sessions_of_country() {
+        let sessions = [
+            (Some("DE"), "de0".to_owned()),
+            (Some("US"), "us0".to_owned()),
+            (Some("DE"), "de1".to_owned()),
+        ];
+
+        let pool = SessionPool::new(sessions, Duration::from_millis(100));
+
+        let cookies: Vec<_> = (0..3)
+            .map(|_| {
+                pool.next_usable(SessionRoute::Country("DE"))
+                    .unwrap()
+                    .cookie()
+            })
+            .collect();
+
+        assert_eq!(cookies, ["de0", "de1", "de0"]);
+        assert_eq!(pool.usable(SessionRoute::Country("US")), 1);
+        assert!(pool.next_usable(SessionRoute::Country("FR")).is_none());
+
+        assert_eq!(pool.replace("us1".to_owned(), Some("US")), Some(1));
+        assert_eq!(pool.replace("fr0".to_owned(), Some("FR")), None);
+    }
+
+    #[tokio::test]
+    async fn fixed_route_uses_one_session() {
+        let pool = pool(3);
+
+        let cookies: Vec<_> = (0..3)
+            .map(|_| pool.next_usable(SessionRoute::Fixed(1)).unwrap().cookie())
+            .collect();
+
+        assert_eq!(cookies, ["session1", "session1", "session1"]);
+
+        pool.expire(&pool.sessions[1]);
+        assert!(pool.next_usable(SessionRoute::Fixed(1)).is_none());
+        assert_eq!(pool.usable(SessionRoute::Fixed(1)), 0);
+    }
+
+    #[tokio::test]
+    async fn each_expiry_is_reported_once() {
+        let sessions = [(Some("DE"), "de".to_owned()), (Some("US"), "us".to_owned())];
+        let pool = SessionPool::new(sessions, Duration::from_millis(100));
+
+        let reported = |pool: &SessionPool| -> Vec<String> {
+            pool.unreported_expiries()
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+
+        pool.expire(&pool.sessions[0]);
+        assert_eq!(reported(&pool), ["#1 (DE)"]);
+        assert!(reported(&pool).is_empty());
+
+        // Another country's session expiring later is still reported
+        pool.expire(&pool.sessions[1]);
+        assert_eq!(reported(&pool), ["#2 (US)"]);
+
+        // Once it works again, a new expiry is reported again
+        pool.replace("de".to_owned(), Some("DE"));
+        pool.sessions[0].record_request(true);
+        pool.expire(&pool.sessions[0]);
+        assert_eq!(reported(&pool), ["#1 (DE)"]);
+    }
+}
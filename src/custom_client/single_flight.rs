@@ -0,0 +1,107 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use hashbrown::HashMap;
+use tokio::sync::broadcast::{self, Sender};
+
+use super::{ClientResult, CustomClientError};
+
+type SharedResult = Result<Bytes, Arc<CustomClientError>>;
+
+/// Lets concurrent callers of the same request share a single in-flight request
+#[derive(Default)]
+pub(super) struct SingleFlight {
+    in_flight: Mutex<HashMap<String, Sender<SharedResult>>>,
+}
+
+enum Role {
+    Leader(Sender<SharedResult>),
+    Follower(broadcast::Receiver<SharedResult>),
+}
+
+impl SingleFlight {
+    /// Run the request unless an identical one is already in flight,
+    /// in which case its result is awaited instead
+    pub(super) async fn run<F, Fut>(&self, key: &str, request: F) -> ClientResult<Bytes>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = ClientResult<Bytes>>,
+    {
+        loop {
+            let role = {
+                let mut in_flight = self.in_flight.lock().unwrap();
+
+                match in_flight.get(key) {
+                    Some(tx) => Role::Follower(tx.subscribe()),
+                    None => {
+                        let (tx, _) = broadcast::channel(1);
+                        in_flight.insert(key.to_owned(), tx.clone());
+
+                        Role::Leader(tx)
+                    }
+                }
+            };
+
+            match role {
+                Role::Leader(tx) => {
+                    // Removes the entry even if the request is cancelled
+                    let guard = InFlightGuard { flight: self, key };
+                    let res = request().await.map_err(Arc::new);
+                    drop(guard);
+
+                    let _ = tx.send(res.clone());
+
+                    return res.map_err(CustomClientError::shared);
+                }
+                Role::Follower(mut rx) => match rx.recv().await {
+                    // The leader's requester was over its cap which doesn't apply to
+                    // this caller so try again under its own requester
+                    Ok(Err(err)) if matches!(*err, CustomClientError::QueueFull) => continue,
+                    Ok(res) => return res.map_err(CustomClientError::shared),
+                    // The leading request was cancelled so try again
+                    Err(_) => continue,
+                },
+            }
+        }
+    }
+}
+
+struct InFlightGuard<'a> {
+    flight: &'a SingleFlight,
+    key: &'a str,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.flight.in_flight.lock().unwrap().remove(self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::Notify;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn followers_retry_when_leader_queue_is_full() {
+        let flight = SingleFlight::default();
+        let notify = Notify::new();
+
+        let leader = flight.run("url", || async {
+            notify.notified().await;
+
+            Err(CustomClientError::QueueFull)
+        });
+
+        let follower = flight.run("url", || async { Ok(Bytes::from_static(b"scores")) });
+
+        let (leader, follower, _) = tokio::join!(leader, follower, async { notify.notify_one() });
+
+        assert!(matches!(leader, Err(CustomClientError::QueueFull)));
+        assert_eq!(follower.unwrap(), "scores");
+    }
+}
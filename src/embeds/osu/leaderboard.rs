@@ -1,31 +1,42 @@
 use std::fmt::{Display, Formatter, Result as FmtResult, Write};
 
 use command_macros::EmbedData;
-use hashbrown::{hash_map::Entry, HashMap};
-use rosu_pp::{Beatmap as Map, BeatmapExt, DifficultyAttributes, ScoreState};
+use rosu_pp::{osu::OsuPerformanceAttributes, PerformanceAttributes};
 use rosu_v2::{
     model::score::Score,
     prelude::{BeatmapExtended, BeatmapsetExtended, GameMode},
 };
 
 use crate::{
-    core::Context,
-    error::PpError,
+    core::{Context, PpSource},
+    custom_client::{LeaderboardScope, LEADERBOARD_LIMIT},
+    pp::{is_lazer_scoring, PpCalculator},
     util::{
         builder::{AuthorBuilder, FooterBuilder},
-        constants::{AVATAR_URL, MAP_THUMB_URL, OSU_BASE},
         datetime::HowLongAgoDynamic,
         numbers::with_comma_int,
-        osu::prepare_beatmap_file,
+        osu::flag_url,
         ModsFormatter, ScoreExt,
     },
-    BotResult,
+    BotResult, CONFIG,
 };
 
-const UNKNOWN_NAME: &str = "<unknown name>";
+pub(super) const UNKNOWN_NAME: &str = "<unknown name>";
+
+/// A user whose score should be highlighted
+pub enum LeaderboardUser {
+    /// Index of the user's score within the leaderboard
+    Listed(usize),
+    /// The user's score if it's not part of the leaderboard
+    Unlisted {
+        score: Box<Score>,
+        global_pos: usize,
+    },
+}
 
 #[derive(EmbedData)]
 pub struct LeaderboardEmbed {
+    title: String,
     description: String,
     thumbnail: String,
     author: AuthorBuilder,
@@ -41,61 +52,87 @@ impl LeaderboardEmbed {
         idx: usize,
         ctx: &Context,
         pages: (usize, usize),
+        scope: LeaderboardScope,
+        country: Option<&str>,
+        user: Option<&LeaderboardUser>,
+        uncertain_below: Option<u32>,
+        detailed: bool,
     ) -> BotResult<Self>
     where
         S: Iterator<Item = &'i Score>,
     {
         let BeatmapsetExtended {
-            artist,
-            title,
             creator_name,
             creator_id,
             ..
         } = map.mapset.as_deref().unwrap();
 
-        let mut author_text = String::with_capacity(32);
+        let description = if scores.is_some() || user.is_some() {
+            let mut calc = PpCalculator::new(ctx, map.map_id).await?;
+            let mut description = String::with_capacity(256);
+            let mut username = String::with_capacity(32);
 
-        if map.mode == GameMode::Mania {
-            let _ = write!(author_text, "[{}K] ", map.cs as u32);
-        }
+            let highlight = match user {
+                Some(LeaderboardUser::Listed(idx)) => Some(*idx),
+                Some(LeaderboardUser::Unlisted { .. }) | None => None,
+            };
+
+            match scores {
+                Some(scores) => {
+                    for (score, i) in scores.zip(idx + 1..) {
+                        write_username(&mut username, score, highlight == Some(i - 1));
+
+                        // Mark ranks that are only approximate
+                        let approx = match uncertain_below {
+                            Some(value) if score.score < value => "~",
+                            Some(_) | None => "",
+                        };
+
+                        let (pp, components) = get_pp(&mut calc, score, detailed);
+
+                        let _ = writeln!(
+                            description,
+                            "**{approx}{i}.** {grade} **{username}**: {score} [ {combo} ] **+{mods}**\n\
+                            - {pp} • {acc:.2}% • {miss}{ago}{components}",
+                            grade = score.grade_emote(map.mode),
+                            score = with_comma_int(score.score),
+                            combo = ComboFormatter::new(score, map),
+                            mods = ModsFormatter::new(&score.mods),
+                            acc = score.accuracy,
+                            miss = MissFormat(score.statistics.miss),
+                            ago = HowLongAgoDynamic::new(&score.ended_at),
+                        );
+                    }
+                }
+                None => description.push_str("No scores found\n"),
+            }
 
-        let _ = write!(
-            author_text,
-            "{artist} - {title} [{version}] [{stars:.2}★]",
-            version = map.version,
-            stars = map.stars
-        );
+            if let Some(LeaderboardUser::Unlisted { score, global_pos }) = user {
+                write_username(&mut username, score, true);
 
-        let description = if let Some(scores) = scores {
-            let map_path = prepare_beatmap_file(ctx, map.map_id).await?;
-            let rosu_map = Map::from_path(map_path).await.map_err(PpError::from)?;
+                // National leaderboards are named after their country if it's known
+                let board = match country {
+                    Some(country) => country.to_owned(),
+                    None => scope.name().to_lowercase(),
+                };
 
-            let mut mod_map = HashMap::new();
-            let mut description = String::with_capacity(256);
-            let mut username = String::with_capacity(32);
+                let listed = if uncertain_below.is_some() {
+                    format!("extended {board} leaderboard")
+                } else {
+                    format!("{board} top {LEADERBOARD_LIMIT}")
+                };
 
-            for (score, i) in scores.zip(idx + 1..) {
-                username.clear();
+                let (pp, components) = get_pp(&mut calc, score, detailed);
 
                 let _ = write!(
-                    username,
-                    "[{name}]({OSU_BASE}users/{id})",
-                    name = score
-                        .user
-                        .as_ref()
-                        .map_or(UNKNOWN_NAME, |user| user.username.as_str()),
-                    id = score.user_id
-                );
-
-                let _ = writeln!(
                     description,
-                    "**{i}.** {grade} **{username}**: {score} [ {combo} ] **+{mods}**\n\
-                    - {pp} • {acc:.2}% • {miss}{ago}",
+                    "\n__Not in {listed}:__\n\
+                    {grade} **{username}**: {score} [ {combo} ] **+{mods}**\n\
+                    - {pp} • {acc:.2}% • {miss}Global #{global_pos} • {ago}{components}",
                     grade = score.grade_emote(map.mode),
                     score = with_comma_int(score.score),
                     combo = ComboFormatter::new(score, map),
                     mods = ModsFormatter::new(&score.mods),
-                    pp = get_pp(&mut mod_map, score, &rosu_map).await,
                     acc = score.accuracy,
                     miss = MissFormat(score.statistics.miss),
                     ago = HowLongAgoDynamic::new(&score.ended_at),
@@ -107,76 +144,198 @@ impl LeaderboardEmbed {
             "No scores found".to_string()
         };
 
-        let mut author = AuthorBuilder::new(author_text).url(format!("{OSU_BASE}b/{}", map.map_id));
+        let mut author = map_author(map);
 
         if let Some(ref author_icon) = author_icon {
             author = author.icon_url(author_icon.to_owned());
         }
 
         let footer_text = format!(
-            "{:?} map by {creator_name} • Page {}/{}",
-            map.status, pages.0, pages.1,
+            "{:?} map by {creator_name} • {} leaderboard • Page {}/{}",
+            map.status,
+            scope.name(),
+            pages.0,
+            pages.1,
         );
 
-        let footer = FooterBuilder::new(footer_text).icon_url(format!("{AVATAR_URL}{creator_id}"));
+        let urls = &CONFIG.get().unwrap().urls;
+        let footer = leaderboard_footer(footer_text, country, *creator_id);
 
         Ok(Self {
+            title: leaderboard_title(scope, country, ""),
             author,
             description,
             footer,
-            thumbnail: format!("{MAP_THUMB_URL}{}l.jpg", map.mapset_id),
+            thumbnail: format!("{}{}l.jpg", urls.map_thumb, map.mapset_id),
         })
     }
 }
 
-async fn get_pp(
-    mod_map: &mut HashMap<u32, (DifficultyAttributes, f32)>,
-    score: &Score,
-    map: &Map,
-) -> PPFormatter {
-    let bits = score.mods.bits();
+/// Title of a leaderboard embed including the country, if any
+pub(super) fn leaderboard_title(
+    scope: LeaderboardScope,
+    country: Option<&str>,
+    suffix: &str,
+) -> String {
+    let mut title = format!("{} leaderboard", scope.name());
 
-    let (attrs, max_pp) = match mod_map.entry(bits) {
-        Entry::Occupied(entry) => {
-            let (attrs, max_pp) = entry.get();
+    if let Some(country) = country {
+        let _ = write!(title, " ({country})");
+    }
 
-            (attrs.to_owned(), *max_pp)
-        }
-        Entry::Vacant(entry) => {
-            let attrs = map.max_pp(bits);
-            let max_pp = attrs.pp() as f32;
-            let (attrs, max_pp) = entry.insert((attrs.into(), max_pp));
+    title.push_str(suffix);
 
-            (attrs.to_owned(), *max_pp)
-        }
+    title
+}
+
+/// Footer with the country's flag as icon or, without valid country, the mapper's avatar
+pub(super) fn leaderboard_footer(
+    text: String,
+    country: Option<&str>,
+    creator_id: u32,
+) -> FooterBuilder {
+    let icon_url = country
+        .and_then(flag_url)
+        .unwrap_or_else(|| format!("{}{creator_id}", CONFIG.get().unwrap().urls.avatar));
+
+    FooterBuilder::new(text).icon_url(icon_url)
+}
+
+fn write_username(username: &mut String, score: &Score, highlight: bool) {
+    username.clear();
+
+    let name = score
+        .user
+        .as_ref()
+        .map_or(UNKNOWN_NAME, |user| user.username.as_str());
+
+    let osu_base = &CONFIG.get().unwrap().urls.osu_base;
+
+    if highlight {
+        let _ = write!(username, "__[{name}]({osu_base}users/{})__", score.user_id);
+    } else {
+        let _ = write!(username, "[{name}]({osu_base}users/{})", score.user_id);
+    }
+}
+
+/// Author containing the map's name, linking to the map
+pub(super) fn map_author(map: &BeatmapExtended) -> AuthorBuilder {
+    let BeatmapsetExtended { artist, title, .. } = map.mapset.as_deref().unwrap();
+
+    let mut author_text = String::with_capacity(32);
+
+    if map.mode == GameMode::Mania {
+        let _ = write!(author_text, "[{}K] ", map.cs as u32);
+    }
+
+    let _ = write!(
+        author_text,
+        "{artist} - {title} [{version}] [{stars:.2}★]",
+        version = map.version,
+        stars = map.stars
+    );
+
+    let osu_base = &CONFIG.get().unwrap().urls.osu_base;
+
+    AuthorBuilder::new(author_text).url(format!("{osu_base}b/{}", map.map_id))
+}
+
+/// The score's pp, its pp as full combo, the map's max pp, and,
+/// if `detailed` is set, the pp components of osu!standard scores
+fn get_pp(calc: &mut PpCalculator, score: &Score, detailed: bool) -> (PPFormatter, PpComponents) {
+    let attrs = calc.score(score).performance();
+    let if_fc = calc.if_fc().map(|attrs| attrs.pp() as f32);
+    let max_pp = calc.max_pp() as f32;
+
+    let osu_attrs = match attrs {
+        PerformanceAttributes::Osu(ref attrs) if detailed => Some(attrs.to_owned()),
+        _ => None,
     };
 
-    let state = ScoreState {
-        max_combo: score.max_combo as usize,
-        n_geki: score.statistics.perfect as usize,
-        n_katu: score.statistics.good as usize,
-        n300: score.statistics.great as usize,
-        n100: score.statistics.ok as usize,
-        n50: score.statistics.meh as usize,
-        n_misses: score.statistics.miss as usize,
+    // Calculated pp of lazer-scoring osu!standard scores miss slider judgements
+    let approx = score.mode == GameMode::Osu && is_lazer_scoring(score);
+
+    let source = CONFIG.get().unwrap().pp_source;
+
+    let pp = PPFormatter {
+        pp: attrs.pp() as f32,
+        approx,
+        api_pp: score.pp,
+        source,
+        if_fc,
+        max_pp,
     };
 
-    let pp = map
-        .pp()
-        .attributes(attrs)
-        .mods(score.mods.bits())
-        .state(state)
-        .calculate()
-        .pp() as f32;
+    // The components don't add up to the API's pp so they're labeled
+    let components = PpComponents {
+        attrs: osu_attrs,
+        calculated: source == PpSource::Api && score.pp.is_some(),
+    };
 
-    PPFormatter(pp, max_pp)
+    (pp, components)
 }
 
-struct PPFormatter(f32, f32);
+struct PPFormatter {
+    /// Calculated pp
+    pp: f32,
+    /// Whether the calculated pp are only approximate
+    approx: bool,
+    api_pp: Option<f32>,
+    source: PpSource,
+    if_fc: Option<f32>,
+    max_pp: f32,
+}
 
 impl Display for PPFormatter {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(f, "**{:.2}**/{:.2}PP", self.0, self.1)
+        let approx = if self.approx { "≈" } else { "" };
+
+        match (self.source, self.api_pp) {
+            (PpSource::Api, Some(api_pp)) => write!(f, "**{api_pp:.2}**/{:.2}PP", self.max_pp)?,
+            (PpSource::Both, Some(api_pp)) => write!(
+                f,
+                "**{approx}{:.2}**/{:.2}PP (API: {api_pp:.2}PP)",
+                self.pp, self.max_pp
+            )?,
+            (_, None) => write!(f, "**{approx}{:.2}**/{:.2}PP", self.pp, self.max_pp)?,
+        }
+
+        if let Some(if_fc) = self.if_fc {
+            write!(f, " ({if_fc:.2}PP for FC)")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Additional line with the individual pp values of an osu!standard score
+struct PpComponents {
+    attrs: Option<OsuPerformanceAttributes>,
+    /// Whether the shown pp are the API's rather than the calculated ones
+    calculated: bool,
+}
+
+impl Display for PpComponents {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let Some(ref attrs) = self.attrs else {
+            return Ok(());
+        };
+
+        write!(
+            f,
+            "\n- Aim {:.2} • Speed {:.2} • Acc {:.2}",
+            attrs.pp_aim, attrs.pp_speed, attrs.pp_acc
+        )?;
+
+        if attrs.pp_flashlight > 0.0 {
+            write!(f, " • FL {:.2}", attrs.pp_flashlight)?;
+        }
+
+        if self.calculated {
+            f.write_str(" (calculated)")?;
+        }
+
+        Ok(())
     }
 }
 
@@ -0,0 +1,182 @@
+use std::fmt::Write;
+
+use command_macros::EmbedData;
+use hashbrown::HashMap;
+use rosu_v2::{
+    model::score::Score,
+    prelude::{BeatmapExtended, BeatmapsetExtended},
+};
+use twilight_model::channel::message::embed::EmbedField;
+
+use crate::{
+    commands::osu::{MinMaxAvg, Number},
+    core::Context,
+    custom_client::LeaderboardScope,
+    pp::{score_pp, PpCalculator},
+    util::{
+        builder::{AuthorBuilder, FooterBuilder},
+        numbers::with_comma_int,
+        ModsFormatter,
+    },
+    BotResult, CONFIG,
+};
+
+use super::leaderboard::{leaderboard_footer, leaderboard_title, map_author, UNKNOWN_NAME};
+
+const MOD_COMBO_LIMIT: usize = 10;
+
+#[derive(EmbedData)]
+pub struct LeaderboardStatsEmbed {
+    title: String,
+    description: String,
+    fields: Vec<EmbedField>,
+    thumbnail: String,
+    author: AuthorBuilder,
+    footer: FooterBuilder,
+}
+
+impl LeaderboardStatsEmbed {
+    pub async fn new(
+        ctx: &Context,
+        map: &BeatmapExtended,
+        scores: &[Score],
+        author_icon: &Option<String>,
+        scope: LeaderboardScope,
+        country: Option<&str>,
+    ) -> BotResult<Self> {
+        let BeatmapsetExtended {
+            creator_name,
+            creator_id,
+            ..
+        } = map.mapset.as_deref().unwrap();
+
+        let urls = &CONFIG.get().unwrap().urls;
+        let mut author = map_author(map);
+
+        if let Some(ref author_icon) = author_icon {
+            author = author.icon_url(author_icon.to_owned());
+        }
+
+        let footer_text = format!("{:?} map by {creator_name}", map.status);
+        let footer = leaderboard_footer(footer_text, country, *creator_id);
+
+        let (description, fields) = if scores.is_empty() {
+            ("No scores found".to_owned(), Vec::new())
+        } else {
+            let description = format!("Statistics of the top {} scores", scores.len());
+
+            (description, Self::fields(ctx, map, scores).await?)
+        };
+
+        Ok(Self {
+            title: leaderboard_title(scope, country, " statistics"),
+            description,
+            fields,
+            thumbnail: format!("{}{}l.jpg", urls.map_thumb, map.mapset_id),
+            author,
+            footer,
+        })
+    }
+
+    async fn fields(
+        ctx: &Context,
+        map: &BeatmapExtended,
+        scores: &[Score],
+    ) -> BotResult<Vec<EmbedField>> {
+        let mut calc = PpCalculator::new(ctx, map.map_id).await?;
+
+        let mut pp = MinMaxAvg::new();
+        let mut acc = MinMaxAvg::new();
+        let mut combo = MinMaxAvg::new();
+        let mut misses = MinMaxAvg::new();
+
+        // Mod combination => (amount of scores, index of the best score)
+        let mut mod_combos: HashMap<String, (usize, usize)> = HashMap::new();
+
+        for (score, i) in scores.iter().zip(0..) {
+            pp.add(score_pp(&mut calc, score));
+            acc.add(score.accuracy);
+            combo.add(score.max_combo);
+            misses.add(score.statistics.miss);
+
+            let mods = ModsFormatter::new(&score.mods).to_string();
+            let (count, best_idx) = mod_combos.entry(mods).or_insert((0, i));
+            *count += 1;
+
+            if scores[*best_idx].score < score.score {
+                *best_idx = i;
+            }
+        }
+
+        let mut mod_combos: Vec<_> = mod_combos.into_iter().collect();
+        mod_combos.sort_unstable_by(|(a_mods, (a_count, _)), (b_mods, (b_count, _))| {
+            b_count.cmp(a_count).then_with(|| a_mods.cmp(b_mods))
+        });
+
+        let mut frequency = String::with_capacity(256);
+        let mut best = String::with_capacity(512);
+        let osu_base = &CONFIG.get().unwrap().urls.osu_base;
+
+        for (mods, (count, best_idx)) in mod_combos.iter().take(MOD_COMBO_LIMIT) {
+            let percent = 100.0 * *count as f32 / scores.len() as f32;
+            let _ = writeln!(frequency, "`{mods}`: {count} ({percent:.0}%)");
+
+            let score = &scores[*best_idx];
+
+            let name = score
+                .user
+                .as_ref()
+                .map_or(UNKNOWN_NAME, |user| user.username.as_str());
+
+            let _ = writeln!(
+                best,
+                "`{mods}`: [{name}]({osu_base}users/{id}) • {score} • {acc:.2}%",
+                id = score.user_id,
+                score = with_comma_int(score.score),
+                acc = score.accuracy,
+            );
+        }
+
+        if mod_combos.len() > MOD_COMBO_LIMIT {
+            let remaining = mod_combos.len() - MOD_COMBO_LIMIT;
+            let _ = write!(frequency, "...and {remaining} more");
+            let _ = write!(best, "...and {remaining} more");
+        }
+
+        let fields = vec![
+            min_avg_max("PP", &pp, |n| format!("{n:.2}pp")),
+            min_avg_max("Accuracy", &acc, |n| format!("{n:.2}%")),
+            min_avg_max("Combo", &combo, |n| format!("{n}x")),
+            min_avg_max("Misses", &misses, |n| n.to_string()),
+            EmbedField {
+                name: "Mod combinations".to_owned(),
+                value: frequency,
+                inline: false,
+            },
+            EmbedField {
+                name: "Best score per mod combination".to_owned(),
+                value: best,
+                inline: false,
+            },
+        ];
+
+        Ok(fields)
+    }
+}
+
+fn min_avg_max<N: Number>(
+    name: &str,
+    values: &MinMaxAvg<N>,
+    fmt: impl Fn(N) -> String,
+) -> EmbedField {
+    EmbedField {
+        name: name.to_owned(),
+        value: format!(
+            "Min: {}\nAvg: {}\nMax: {}",
+            fmt(values.min()),
+            fmt(values.avg()),
+            fmt(values.max()),
+        ),
+        inline: true,
+    }
+}
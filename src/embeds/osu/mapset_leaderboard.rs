@@ -0,0 +1,111 @@
+use std::fmt::Write;
+
+use command_macros::EmbedData;
+use rosu_v2::{
+    model::score::Score,
+    prelude::{BeatmapsetExtended, GameMode},
+};
+
+use crate::{
+    custom_client::LeaderboardScope,
+    util::{
+        builder::{AuthorBuilder, FooterBuilder},
+        numbers::with_comma_int,
+        ModsFormatter, ScoreExt,
+    },
+    CONFIG,
+};
+
+use super::leaderboard::{leaderboard_footer, leaderboard_title, UNKNOWN_NAME};
+
+/// Maximum amount of difficulties whose leaderboard is requested
+pub const MAPSET_DIFF_LIMIT: usize = 15;
+
+pub struct MapsetLeaderboardEntry {
+    pub map_id: u32,
+    pub version: String,
+    pub mode: GameMode,
+    pub stars: f32,
+    /// The difficulty's first place and its pp
+    pub top: Option<(Score, f32)>,
+}
+
+#[derive(EmbedData)]
+pub struct MapsetLeaderboardEmbed {
+    title: String,
+    description: String,
+    thumbnail: String,
+    author: AuthorBuilder,
+    footer: FooterBuilder,
+}
+
+impl MapsetLeaderboardEmbed {
+    pub fn new(
+        mapset: &BeatmapsetExtended,
+        entries: &[MapsetLeaderboardEntry],
+        total_diffs: usize,
+        scope: LeaderboardScope,
+        country: Option<&str>,
+    ) -> Self {
+        let urls = &CONFIG.get().unwrap().urls;
+        let osu_base = &urls.osu_base;
+        let mut description = String::with_capacity(entries.len() * 192);
+
+        for (entry, i) in entries.iter().zip(1..) {
+            let _ = writeln!(
+                description,
+                "**{i}.** [{version}]({osu_base}b/{map_id}) [{stars:.2}★]",
+                version = entry.version,
+                map_id = entry.map_id,
+                stars = entry.stars,
+            );
+
+            match entry.top {
+                Some((ref score, pp)) => {
+                    let name = score
+                        .user
+                        .as_ref()
+                        .map_or(UNKNOWN_NAME, |user| user.username.as_str());
+
+                    let _ = writeln!(
+                        description,
+                        "- {grade} [{name}]({osu_base}users/{user_id}): {score} • \
+                        **{pp:.2}PP** • {acc:.2}% • **+{mods}**",
+                        grade = score.grade_emote(entry.mode),
+                        user_id = score.user_id,
+                        score = with_comma_int(score.score),
+                        acc = score.accuracy,
+                        mods = ModsFormatter::new(&score.mods),
+                    );
+                }
+                None => description.push_str("- No scores found\n"),
+            }
+        }
+
+        if total_diffs > entries.len() {
+            let _ = write!(
+                description,
+                "...and {} more difficulties",
+                total_diffs - entries.len()
+            );
+        }
+
+        let author = AuthorBuilder::new(format!("{} - {}", mapset.artist, mapset.title))
+            .url(format!("{osu_base}s/{}", mapset.mapset_id));
+
+        let footer_text = format!(
+            "{:?} mapset by {} • Specify a difficulty to see its full leaderboard",
+            mapset.status, mapset.creator_name,
+        );
+
+        let footer = leaderboard_footer(footer_text, country, mapset.creator_id);
+
+        Self {
+            title: leaderboard_title(scope, country, " summary"),
+            description,
+            thumbnail: format!("{}{}l.jpg", urls.map_thumb, mapset.mapset_id),
+            author,
+            footer,
+        }
+    }
+}
@@ -1,3 +1,5 @@
 mod leaderboard;
+mod leaderboard_stats;
+mod mapset_leaderboard;
 
-pub use self::leaderboard::*;
+pub use self::{leaderboard::*, leaderboard_stats::*, mapset_leaderboard::*};
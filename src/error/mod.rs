@@ -29,8 +29,6 @@ pub enum Error {
     MissingEnvVariable(&'static str),
     #[error("event was expected to contain member or user but contained neither")]
     MissingAuthor,
-    #[error("osu error")]
-    Osu(#[from] rosu_v2::error::OsuError),
     #[error("failed to parse env variable `{name}={value}`; expected {expected}")]
     ParsingEnvVariable {
         name: &'static str,
@@ -61,6 +61,9 @@ async fn async_main() -> eyre::Result<()> {
         .await
         .wrap_err("failed to register slash commands")?;
 
+    let cache_ctx = Arc::clone(&ctx);
+    tokio::spawn(async move { cache_ctx.cache.persist_periodically().await });
+
     let event_ctx = Arc::clone(&ctx);
 
     tokio::select! {
@@ -73,6 +76,7 @@ async fn async_main() -> eyre::Result<()> {
     }
 
     Context::down(&mut shards).await;
+    ctx.cache.persist().await;
 
     info!("Shutting down");
 
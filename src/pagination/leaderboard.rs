@@ -4,11 +4,18 @@ use command_macros::BasePagination;
 use rosu_v2::{model::score::Score, prelude::BeatmapExtended};
 use twilight_model::channel::Message;
 
-use crate::{core::Context, embeds::LeaderboardEmbed, BotResult};
+use crate::{
+    core::Context,
+    custom_client::LeaderboardScope,
+    embeds::{LeaderboardEmbed, LeaderboardUser},
+    util::numbers,
+    BotResult,
+};
 
 use super::{Pages, Pagination};
 
 #[derive(BasePagination)]
+#[jump_idx(user_idx)]
 pub struct LeaderboardPagination {
     ctx: Arc<Context>,
     msg: Message,
@@ -16,22 +23,52 @@ pub struct LeaderboardPagination {
     map: BeatmapExtended,
     scores: Vec<Score>,
     first_place_icon: Option<String>,
+    scope: LeaderboardScope,
+    country: Option<&'static str>,
+    user: Option<LeaderboardUser>,
+    user_idx: Option<usize>,
+    uncertain_below: Option<u32>,
+    detailed: bool,
 }
 
 impl LeaderboardPagination {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         msg: Message,
         map: BeatmapExtended,
         scores: Vec<Score>,
         first_place_icon: Option<String>,
+        scope: LeaderboardScope,
+        country: Option<&'static str>,
+        user: Option<LeaderboardUser>,
+        uncertain_below: Option<u32>,
+        detailed: bool,
         ctx: Arc<Context>,
     ) -> Self {
+        let mut pages = Pages::new(10, scores.len());
+
+        let user_idx = match user {
+            Some(LeaderboardUser::Listed(idx)) => Some(idx),
+            Some(LeaderboardUser::Unlisted { .. }) | None => None,
+        };
+
+        // Start on the page of the user's score
+        if let Some(idx) = user_idx {
+            pages.index = numbers::last_multiple(pages.per_page, idx + 1);
+        }
+
         Self {
             msg,
-            pages: Pages::new(10, scores.len()),
+            pages,
             map,
             scores,
             first_place_icon,
+            scope,
+            country,
+            user,
+            user_idx,
+            uncertain_below,
+            detailed,
             ctx,
         }
     }
@@ -54,6 +91,11 @@ impl Pagination for LeaderboardPagination {
             self.pages.index,
             &self.ctx,
             (self.page(), self.pages.total_pages),
+            self.scope,
+            self.country,
+            self.user.as_ref(),
+            self.uncertain_below,
+            self.detailed,
         );
 
         embed_fut.await
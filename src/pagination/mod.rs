@@ -10,8 +10,16 @@ use tokio_stream::StreamExt;
 use twilight_gateway::Event;
 use twilight_http::{error::ErrorType, request::channel::reaction::RequestReactionType};
 use twilight_model::{
-    channel::{message::ReactionType, Message},
+    application::interaction::{Interaction, InteractionData},
+    channel::{
+        message::{
+            component::{ActionRow, Button, ButtonStyle, TextInput, TextInputStyle},
+            Component, MessageFlags, ReactionType,
+        },
+        Message,
+    },
     gateway::GatewayReaction,
+    http::interaction::{InteractionResponse, InteractionResponseData, InteractionResponseType},
     id::{marker::UserMarker, Id},
 };
 
@@ -27,6 +35,35 @@ pub use self::leaderboard::LeaderboardPagination;
 type ReactionVec = SmallVec<[RequestReactionType<'static>; 7]>;
 type PaginationResult = Result<(), PaginationError>;
 
+/// Emotes of all pagination controls and the custom ids of their buttons
+const CONTROLS: [(&str, &str); 7] = [
+    ("⏮", "pagination_start"),
+    ("⏪", "pagination_multi_back"),
+    ("◀", "pagination_back"),
+    ("🎯", CUSTOM_CONTROL),
+    ("▶", "pagination_next"),
+    ("⏩", "pagination_multi_next"),
+    ("⏭", "pagination_end"),
+];
+
+const CUSTOM_CONTROL: &str = "pagination_custom";
+const CUSTOM_LABEL: &str = "My score";
+
+/// Discord allows at most five buttons per action row
+const BUTTONS_PER_ROW: usize = 5;
+
+const JUMP_BUTTON: &str = "pagination_jump";
+const JUMP_MODAL: &str = "pagination_jump_modal";
+const JUMP_INPUT: &str = "pagination_jump_input";
+
+/// How users can navigate through the pages
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PaginationStyle {
+    #[default]
+    Buttons,
+    Reactions,
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("pagination error")]
 pub enum PaginationError {
@@ -83,6 +120,14 @@ pub trait BasePagination {
     fn index_mut(&mut self) -> &mut usize {
         &mut self.pages_mut().index
     }
+
+    /// Index of the page that contains the given entry index,
+    /// or `None` if there is no such page
+    fn page_index_of(&self, index: usize) -> Option<usize> {
+        let page_index = numbers::last_multiple(self.per_page(), index + 1);
+
+        (page_index <= self.last_index()).then_some(page_index)
+    }
 }
 
 pub trait Pagination: BasePagination + Send + Sync + Sized {
@@ -98,17 +143,41 @@ pub trait Pagination: BasePagination + Send + Sync + Sized {
 
     fn process_data(&mut self, _data: &Self::PageData) {}
 
+    /// Move to the page that contains the entry at the given index.
+    ///
+    /// Returns `None` if the index is out of bounds.
+    fn jump_to(&mut self, index: usize) -> Option<PageChange> {
+        let page_index = self.page_index_of(index)?;
+
+        if page_index == self.index() {
+            return Some(PageChange::None);
+        }
+
+        *self.index_mut() = page_index;
+
+        Some(PageChange::Change)
+    }
+
     fn final_processing(self, _ctx: &Context) -> impl Future<Output = BotResult<()>> + Send {
         future::ready(Ok(()))
     }
 
     // Don't implement anything else
-    fn start(self, ctx: Arc<Context>, owner: Id<UserMarker>, duration: u64)
+    fn start(self, ctx: Arc<Context>, owner: Id<UserMarker>, style: PaginationStyle, duration: u64)
     where
         Self: 'static,
     {
         tokio::spawn(async move {
-            if let Err(err) = start_pagination(self, &ctx, owner, duration).await {
+            let res = match style {
+                PaginationStyle::Buttons => {
+                    start_button_pagination(self, &ctx, owner, duration).await
+                }
+                PaginationStyle::Reactions => {
+                    start_reaction_pagination(self, &ctx, owner, duration).await
+                }
+            };
+
+            if let Err(err) = res {
                 warn!("{:?}", Report::new(err));
             }
         });
@@ -124,6 +193,60 @@ pub trait Pagination: BasePagination + Send + Sync + Sized {
     fn page(&self) -> usize {
         self.index() / self.per_page() + 1
     }
+
+    /// Buttons corresponding to the reactions, disabled if they wouldn't change the page
+    fn components(&self) -> Vec<Component> {
+        let mut buttons: Vec<_> = self
+            .reactions()
+            .iter()
+            .filter_map(|reaction| match reaction {
+                RequestReactionType::Unicode { name } => CONTROLS
+                    .iter()
+                    .find(|(emote, _)| emote == name)
+                    .map(|&(emote, custom_id)| (emote, custom_id)),
+                RequestReactionType::Custom { .. } => None,
+            })
+            .map(|(emote, custom_id)| {
+                // The jump to a specific position is labeled instead of using its reaction
+                let (emoji, label) = if custom_id == CUSTOM_CONTROL {
+                    (None, Some(CUSTOM_LABEL.to_owned()))
+                } else {
+                    let emoji = ReactionType::Unicode {
+                        name: emote.to_owned(),
+                    };
+
+                    (Some(emoji), None)
+                };
+
+                Component::Button(Button {
+                    custom_id: Some(custom_id.to_owned()),
+                    disabled: action_index(self, emote).is_none(),
+                    emoji,
+                    label,
+                    style: ButtonStyle::Secondary,
+                    url: None,
+                })
+            })
+            .collect();
+
+        buttons.push(Component::Button(Button {
+            custom_id: Some(JUMP_BUTTON.to_owned()),
+            disabled: self.pages().total_pages <= 1,
+            emoji: None,
+            label: Some("Go to...".to_owned()),
+            style: ButtonStyle::Secondary,
+            url: None,
+        }));
+
+        buttons
+            .chunks(BUTTONS_PER_ROW)
+            .map(|row| {
+                Component::ActionRow(ActionRow {
+                    components: row.to_vec(),
+                })
+            })
+            .collect()
+    }
 }
 
 #[derive(Eq, PartialEq)]
@@ -154,7 +277,7 @@ impl Pages {
     }
 }
 
-async fn start_pagination<P: Pagination + Send>(
+async fn start_reaction_pagination<P: Pagination + Send>(
     mut pagination: P,
     ctx: &Context,
     owner: Id<UserMarker>,
@@ -218,6 +341,69 @@ async fn start_pagination<P: Pagination + Send>(
         .map_err(PaginationError::Bot)
 }
 
+async fn start_button_pagination<P: Pagination + Send>(
+    mut pagination: P,
+    ctx: &Context,
+    owner: Id<UserMarker>,
+    duration: u64,
+) -> PaginationResult {
+    let component_stream = {
+        let msg = pagination.msg();
+        let components = pagination.components();
+
+        ctx.http
+            .update_message(msg.channel_id, msg.id)
+            .components(Some(&components))
+            .map_err(Error::from)?
+            .await?;
+
+        let msg_id = msg.id;
+
+        // Modal submits are not considered components by standby
+        // so all interactions need to be checked manually
+        ctx.standby
+            .wait_for_event_stream(move |event: &Event| match event {
+                Event::InteractionCreate(event) => {
+                    let is_control = matches!(
+                        event.data,
+                        Some(
+                            InteractionData::MessageComponent(_) | InteractionData::ModalSubmit(_)
+                        )
+                    );
+
+                    is_control && event.message.as_ref().map(|msg| msg.id) == Some(msg_id)
+                }
+                _ => false,
+            })
+            .map(|event| match event {
+                Event::InteractionCreate(event) => event.0,
+                _ => unreachable!(),
+            })
+            .timeout(Duration::from_secs(duration))
+    };
+
+    tokio::pin!(component_stream);
+
+    while let Some(Ok(component)) = component_stream.next().await {
+        if let Err(err) = next_page_component(&mut pagination, component, owner, ctx).await {
+            warn!("{:?}", Report::new(err).wrap_err("error while paginating"));
+        }
+    }
+
+    let msg = pagination.msg();
+
+    ctx.http
+        .update_message(msg.channel_id, msg.id)
+        .components(Some(&[]))
+        .map_err(Error::from)?
+        .await?;
+
+    pagination
+        .final_processing(ctx)
+        .await
+        .map_err(PaginationError::Bot)
+}
+
 async fn next_page<P: Pagination>(
     pagination: &mut P,
     reaction: GatewayReaction,
@@ -242,59 +428,170 @@ async fn next_page<P: Pagination>(
     Ok(())
 }
 
-async fn process_reaction<P: Pagination>(
+async fn next_page_component<P: Pagination>(
     pagination: &mut P,
-    reaction: &ReactionType,
-) -> PageChange {
-    let change_result = match reaction {
-        ReactionType::Unicode { name } => match name.as_str() {
-            // Move to start
-            "⏮" => (pagination.index() != 0).then_some(0),
-            // Move one page left
-            "⏪" => match pagination.index() {
-                0 => None,
-                idx => Some(idx.saturating_sub(pagination.multi_step())),
-            },
-            // Move one index left
-            "◀" => match pagination.index() {
-                0 => None,
-                idx => Some(idx.saturating_sub(pagination.single_step())),
-            },
-            // Move to specific position
-            "*" => {
-                if let Some(index) = pagination.jump_index() {
-                    let i = numbers::last_multiple(pagination.per_page(), index + 1);
-
-                    if i != pagination.index() {
-                        Some(i)
-                    } else {
-                        None
+    component: Interaction,
+    owner: Id<UserMarker>,
+    ctx: &Context,
+) -> BotResult<()> {
+    let response = if component.author_id() != Some(owner) {
+        ephemeral_response("Only the user who invoked the command can use these buttons")
+    } else {
+        match component.data {
+            Some(InteractionData::MessageComponent(ref data)) if data.custom_id == JUMP_BUTTON => {
+                jump_modal_response()
+            }
+            Some(InteractionData::ModalSubmit(ref data)) if data.custom_id == JUMP_MODAL => {
+                let input = data
+                    .components
+                    .iter()
+                    .flat_map(|row| row.components.iter())
+                    .find(|component| component.custom_id == JUMP_INPUT)
+                    .and_then(|component| component.value.as_deref())
+                    .unwrap_or_default();
+
+                match parse_jump_input(input, pagination.per_page()) {
+                    Some(index) => match pagination.jump_to(index) {
+                        Some(change) => page_response(pagination, change).await?,
+                        None => {
+                            let content =
+                                format!("There are only {} pages", pagination.pages().total_pages);
+
+                            ephemeral_response(content)
+                        }
+                    },
+                    None => {
+                        let content = format!(
+                            "Failed to parse `{input}`. \
+                            Specify either a page number like `3` or a rank like `#25`."
+                        );
+
+                        ephemeral_response(content)
                     }
-                } else {
-                    None
                 }
             }
-            // Move one index right
-            "▶" => (pagination.index() != pagination.last_index()).then(|| {
-                pagination
-                    .last_index()
-                    .min(pagination.index() + pagination.single_step())
-            }),
-            // Move one page right
-            "⏩" => (pagination.index() != pagination.last_index()).then(|| {
-                pagination
-                    .last_index()
-                    .min(pagination.index() + pagination.multi_step())
-            }),
-            // Move to end
-            "⏭" => {
-                (pagination.index() != pagination.last_index()).then(|| pagination.last_index())
+            _ => {
+                let change = process_component(pagination, &component);
+
+                page_response(pagination, change).await?
             }
-            _ => None,
-        },
+        }
+    };
+
+    ctx.interaction()
+        .create_response(component.id, &component.token, &response)
+        .await?;
+
+    Ok(())
+}
+
+/// Update the message if the page changed, otherwise just acknowledge the interaction
+async fn page_response<P: Pagination>(
+    pagination: &mut P,
+    change: PageChange,
+) -> BotResult<InteractionResponse> {
+    if change == PageChange::None {
+        return Ok(InteractionResponse {
+            kind: InteractionResponseType::DeferredUpdateMessage,
+            data: None,
+        });
+    }
+
+    let data = pagination.build_page().await?;
+    pagination.process_data(&data);
+
+    let data = InteractionResponseData {
+        components: Some(pagination.components()),
+        content: pagination.content().map(Cow::into_owned),
+        embeds: Some(vec![data.build()]),
+        ..Default::default()
+    };
+
+    Ok(InteractionResponse {
+        kind: InteractionResponseType::UpdateMessage,
+        data: Some(data),
+    })
+}
+
+fn ephemeral_response(content: impl Into<String>) -> InteractionResponse {
+    let data = InteractionResponseData {
+        content: Some(content.into()),
+        flags: Some(MessageFlags::EPHEMERAL),
+        ..Default::default()
+    };
+
+    InteractionResponse {
+        kind: InteractionResponseType::ChannelMessageWithSource,
+        data: Some(data),
+    }
+}
+
+fn jump_modal_response() -> InteractionResponse {
+    let input = TextInput {
+        custom_id: JUMP_INPUT.to_owned(),
+        label: "Page number or rank".to_owned(),
+        max_length: Some(5),
+        min_length: Some(1),
+        placeholder: Some("e.g. 3 for page 3 or #25 for rank 25".to_owned()),
+        required: Some(true),
+        style: TextInputStyle::Short,
+        value: None,
+    };
+
+    let data = InteractionResponseData {
+        components: Some(vec![Component::ActionRow(ActionRow {
+            components: vec![Component::TextInput(input)],
+        })]),
+        custom_id: Some(JUMP_MODAL.to_owned()),
+        title: Some("Jump to page".to_owned()),
+        ..Default::default()
+    };
+
+    InteractionResponse {
+        kind: InteractionResponseType::Modal,
+        data: Some(data),
+    }
+}
+
+/// Parse either a page number like `3` or a rank like `#25` into an entry index
+fn parse_jump_input(input: &str, per_page: usize) -> Option<usize> {
+    let input = input.trim();
+
+    match input.strip_prefix('#') {
+        Some(rank) => rank.trim().parse::<usize>().ok()?.checked_sub(1),
+        None => input
+            .parse::<usize>()
+            .ok()?
+            .checked_sub(1)
+            .map(|page| page * per_page),
+    }
+}
+
+async fn process_reaction<P: Pagination>(
+    pagination: &mut P,
+    reaction: &ReactionType,
+) -> PageChange {
+    let change_result = match reaction {
+        ReactionType::Unicode { name } => action_index(pagination, name),
+        _ => None,
+    };
+
+    apply_change(pagination, change_result)
+}
+
+fn process_component<P: Pagination>(pagination: &mut P, component: &Interaction) -> PageChange {
+    let change_result = match component.data {
+        Some(InteractionData::MessageComponent(ref data)) => CONTROLS
+            .iter()
+            .find(|(_, custom_id)| *custom_id == data.custom_id)
+            .and_then(|(emote, _)| action_index(pagination, emote)),
         _ => None,
     };
 
+    apply_change(pagination, change_result)
+}
+
+fn apply_change<P: Pagination>(pagination: &mut P, change_result: Option<usize>) -> PageChange {
     match change_result {
         Some(index) => {
             *pagination.index_mut() = index;
@@ -305,6 +602,45 @@ async fn process_reaction<P: Pagination>(
     }
 }
 
+/// The index that the control with the given emote leads to,
+/// or `None` if it wouldn't change the page
+fn action_index<P: BasePagination + ?Sized>(pagination: &P, emote: &str) -> Option<usize> {
+    match emote {
+        // Move to start
+        "⏮" => (pagination.index() != 0).then_some(0),
+        // Move one page left
+        "⏪" => match pagination.index() {
+            0 => None,
+            idx => Some(idx.saturating_sub(pagination.multi_step())),
+        },
+        // Move one index left
+        "◀" => match pagination.index() {
+            0 => None,
+            idx => Some(idx.saturating_sub(pagination.single_step())),
+        },
+        // Move to specific position
+        "🎯" => pagination
+            .jump_index()
+            .and_then(|index| pagination.page_index_of(index))
+            .filter(|&index| index != pagination.index()),
+        // Move one index right
+        "▶" => (pagination.index() != pagination.last_index()).then(|| {
+            pagination
+                .last_index()
+                .min(pagination.index() + pagination.single_step())
+        }),
+        // Move one page right
+        "⏩" => (pagination.index() != pagination.last_index()).then(|| {
+            pagination
+                .last_index()
+                .min(pagination.index() + pagination.multi_step())
+        }),
+        // Move to end
+        "⏭" => (pagination.index() != pagination.last_index()).then(|| pagination.last_index()),
+        _ => None,
+    }
+}
+
 #[derive(Debug)]
 enum ReactionWrapper {
     Add(GatewayReaction),
@@ -318,3 +654,36 @@ impl ReactionWrapper {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether the text is a single emoji, possibly followed by a variation selector
+    fn is_emoji(text: &str) -> bool {
+        let mut chars = text.chars();
+
+        let emoji = chars.next().map_or(
+            false,
+            |c| matches!(c, '\u{2190}'..='\u{2BFF}' | '\u{1F300}'..='\u{1FAFF}'),
+        );
+
+        emoji && chars.all(|c| c == '\u{FE0F}')
+    }
+
+    #[test]
+    fn controls_are_emojis() {
+        for (emote, custom_id) in CONTROLS {
+            assert!(is_emoji(emote), "control `{custom_id}` has no emoji");
+        }
+    }
+
+    #[test]
+    fn controls_are_unique() {
+        for (i, (emote, custom_id)) in CONTROLS.iter().enumerate() {
+            assert!(CONTROLS[i + 1..]
+                .iter()
+                .all(|(other_emote, other_id)| other_emote != emote && other_id != custom_id));
+        }
+    }
+}
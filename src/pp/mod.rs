@@ -1,24 +1,28 @@
-use rosu_pp::{Beatmap, BeatmapExt as rosu_v2BeatmapExt, DifficultyAttributes};
-use rosu_v2::model::mods::GameModsIntermode;
+use hashbrown::HashMap;
+use rosu_pp::{
+    Beatmap, BeatmapExt as rosu_v2BeatmapExt, DifficultyAttributes, PerformanceAttributes,
+    ScoreState,
+};
+use rosu_v2::prelude::{GameModIntermode, GameMode, GameModsIntermode, Score};
 
-use crate::{core::Context, error::PpError, util::osu::prepare_beatmap_file};
+use crate::{
+    core::{Context, PpSource},
+    error::PpError,
+    util::osu::prepare_beatmap_file,
+    CONFIG,
+};
 
-enum ScoreKind {
-    Mods(GameModsIntermode),
-}
+pub use self::settings::ModSettings;
 
-impl ScoreKind {
-    fn mods(&self) -> u32 {
-        match self {
-            Self::Mods(mods) => mods.bits(),
-        }
-    }
-}
+mod settings;
 
 pub struct PpCalculator {
     map: Beatmap,
-    score: Option<ScoreKind>,
-    difficulty: Option<DifficultyAttributes>,
+    mods: ModSettings,
+    /// Hit results of the score, if a score was given instead of just mods
+    state: Option<ScoreState>,
+    /// Difficulty attributes only depend on the mods so they are kept around for each
+    difficulties: HashMap<ModSettings, DifficultyAttributes>,
 }
 
 impl PpCalculator {
@@ -28,26 +32,244 @@ impl PpCalculator {
 
         Ok(Self {
             map,
-            score: None,
-            difficulty: None,
+            mods: ModSettings::default(),
+            state: None,
+            difficulties: HashMap::new(),
         })
     }
 
     pub fn mods(&mut self, mods: GameModsIntermode) -> &mut Self {
-        self.score = Some(ScoreKind::Mods(mods));
-        self.difficulty = None;
+        self.mods = ModSettings::from_intermode(&mods);
+        self.state = None;
 
         self
     }
 
-    pub fn stars(&mut self) -> f64 {
-        let mods = self.score.as_ref().map(ScoreKind::mods).unwrap_or_default();
+    pub fn score(&mut self, score: &Score) -> &mut Self {
+        self.mods = ModSettings::new(&score.mods);
+        self.state = Some(score_state(score));
 
-        let difficulty = &mut self.difficulty;
+        self
+    }
+
+    fn difficulty(&mut self) -> &DifficultyAttributes {
+        let mods = self.mods;
         let map = &self.map;
 
-        difficulty
-            .get_or_insert_with(|| map.stars().mods(mods).calculate())
-            .stars()
+        self.difficulties.entry(mods).or_insert_with(|| {
+            let map = mods.adjust(map);
+            let calc = map.stars().mods(mods.bits());
+
+            match mods.clock_rate() {
+                Some(clock_rate) => calc.clock_rate(clock_rate).calculate(),
+                None => calc.calculate(),
+            }
+        })
+    }
+
+    pub fn stars(&mut self) -> f64 {
+        self.difficulty().stars()
+    }
+
+    pub fn pp(&mut self) -> f64 {
+        self.performance().pp()
+    }
+
+    /// Performance of the score, including the individual pp components
+    pub fn performance(&mut self) -> PerformanceAttributes {
+        self.calculate(self.state.clone())
+    }
+
+    /// The pp of an SS with the current mods
+    pub fn max_pp(&mut self) -> f64 {
+        self.calculate(None).pp()
+    }
+
+    /// Performance of the score if it was a full combo with its misses
+    /// turned into hits so that the accuracy stays about the same.
+    ///
+    /// Returns `None` if there is no score, if it already is a full combo,
+    /// or for osu!mania whose pp don't depend on combo.
+    pub fn if_fc(&mut self) -> Option<PerformanceAttributes> {
+        let state = self.state.clone()?;
+        let fc_state = if_fc_state(self.difficulty(), state)?;
+
+        Some(self.calculate(Some(fc_state)))
+    }
+
+    fn calculate(&mut self, state: Option<ScoreState>) -> PerformanceAttributes {
+        let mods = self.mods;
+        let attrs = self.difficulty().to_owned();
+
+        // The difficulty attributes already include the adjusted map attributes
+        let mut calc = self.map.pp().attributes(attrs).mods(mods.bits());
+
+        if let Some(clock_rate) = mods.clock_rate() {
+            calc = calc.clock_rate(clock_rate);
+        }
+
+        match state {
+            Some(state) => calc.state(state).calculate(),
+            None => calc.calculate(),
+        }
+    }
+}
+
+/// Hit results of a score as required by rosu-pp.
+///
+/// Scores come with lazer statistics so e.g. catch droplets are large ticks
+/// and tiny droplets are small ticks.
+pub fn score_state(score: &Score) -> ScoreState {
+    let stats = &score.statistics;
+    let max_combo = score.max_combo as usize;
+
+    match score.mode {
+        GameMode::Osu | GameMode::Taiko => ScoreState {
+            max_combo,
+            n300: stats.great as usize,
+            n100: stats.ok as usize,
+            n50: stats.meh as usize,
+            n_misses: stats.miss as usize,
+            ..Default::default()
+        },
+        GameMode::Catch => ScoreState {
+            max_combo,
+            n300: stats.great as usize,
+            n100: stats.large_tick_hit as usize,
+            n50: stats.small_tick_hit as usize,
+            // Missed tiny droplets
+            n_katu: stats.small_tick_miss as usize,
+            // Missed droplets break combo just like missed fruits
+            n_misses: (stats.miss + stats.large_tick_miss) as usize,
+            ..Default::default()
+        },
+        GameMode::Mania => ScoreState {
+            max_combo,
+            n_geki: stats.perfect as usize,
+            n_katu: stats.good as usize,
+            n300: stats.great as usize,
+            n100: stats.ok as usize,
+            n50: stats.meh as usize,
+            n_misses: stats.miss as usize,
+        },
+    }
+}
+
+/// Whether the score was set with lazer's scoring instead of the classic one,
+/// i.e. its slider tails and ticks are judged.
+///
+/// rosu-pp only considers classic judgements so the calculated pp of such
+/// osu!standard scores can differ from the ones osu! awards.
+pub fn is_lazer_scoring(score: &Score) -> bool {
+    score.legacy_score_id.is_none() && !score.mods.contains_intermode(GameModIntermode::Classic)
+}
+
+/// The pp that a score is shown and ranked with according to the configured [`PpSource`]
+pub fn score_pp(calc: &mut PpCalculator, score: &Score) -> f32 {
+    match (CONFIG.get().unwrap().pp_source, score.pp) {
+        (PpSource::Api, Some(pp)) => pp,
+        _ => calc.score(score).pp() as f32,
+    }
+}
+
+/// Hit results of the score as if it had been a full combo.
+///
+/// Returns `None` if it already is one or for osu!mania.
+fn if_fc_state(attrs: &DifficultyAttributes, mut state: ScoreState) -> Option<ScoreState> {
+    let max_combo = attrs.max_combo();
+
+    if state.n_misses == 0 && state.max_combo >= max_combo {
+        return None;
+    }
+
+    match attrs {
+        DifficultyAttributes::Osu(_) | DifficultyAttributes::Taiko(_) => {
+            // Misses become 300s and 100s in the ratio of the score's hits
+            let hits = state.n300 + state.n100 + state.n50;
+
+            let new_100s = if hits == 0 {
+                0
+            } else {
+                let ratio = (state.n100 + state.n50) as f64 / hits as f64;
+
+                (ratio * state.n_misses as f64).round() as usize
+            };
+
+            state.n300 += state.n_misses - new_100s;
+            state.n100 += new_100s;
+        }
+        // Missed fruits and droplets are simply caught instead
+        DifficultyAttributes::Catch(attrs) => {
+            state.n300 = attrs.n_fruits;
+            state.n100 = attrs.n_droplets;
+        }
+        DifficultyAttributes::Mania(_) => return None,
+    }
+
+    state.n_misses = 0;
+    state.max_combo = max_combo;
+
+    Some(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use rosu_pp::{catch::CatchDifficultyAttributes, osu::OsuDifficultyAttributes};
+
+    use super::*;
+
+    fn state(
+        n300: usize,
+        n100: usize,
+        n50: usize,
+        n_misses: usize,
+        max_combo: usize,
+    ) -> ScoreState {
+        ScoreState {
+            max_combo,
+            n300,
+            n100,
+            n50,
+            n_misses,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn misses_keep_hit_ratio() {
+        let attrs = DifficultyAttributes::Osu(OsuDifficultyAttributes {
+            max_combo: 1000,
+            ..Default::default()
+        });
+
+        // A fifth of the hits are 100s or 50s so a fifth of the misses become 100s
+        let fc = if_fc_state(&attrs, state(400, 80, 20, 10, 300)).unwrap();
+
+        assert_eq!((fc.n300, fc.n100, fc.n50, fc.n_misses), (408, 82, 20, 0));
+        assert_eq!(fc.max_combo, 1000);
+    }
+
+    #[test]
+    fn full_combo_has_no_if_fc() {
+        let attrs = DifficultyAttributes::Osu(OsuDifficultyAttributes {
+            max_combo: 1000,
+            ..Default::default()
+        });
+
+        assert!(if_fc_state(&attrs, state(900, 100, 0, 0, 1000)).is_none());
+        assert!(if_fc_state(&attrs, state(900, 100, 0, 0, 700)).is_some());
+    }
+
+    #[test]
+    fn catch_fc_catches_everything() {
+        let attrs = DifficultyAttributes::Catch(CatchDifficultyAttributes {
+            n_fruits: 300,
+            n_droplets: 50,
+            ..Default::default()
+        });
+
+        let fc = if_fc_state(&attrs, state(290, 45, 100, 15, 200)).unwrap();
+
+        assert_eq!((fc.n300, fc.n100, fc.n_misses), (300, 50, 0));
     }
 }
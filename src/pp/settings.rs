@@ -0,0 +1,236 @@
+use std::{
+    borrow::Cow,
+    hash::{Hash, Hasher},
+};
+
+use rosu_pp::Beatmap;
+use rosu_v2::model::mods::{
+    generated_mods::{
+        DifficultyAdjustCatch, DifficultyAdjustMania, DifficultyAdjustOsu, DifficultyAdjustTaiko,
+        GameMod,
+    },
+    GameMods, GameModsIntermode,
+};
+
+use crate::util::osu::speed_change;
+
+/// Everything about a score's mods that affects its difficulty:
+/// the legacy mod bits, a custom clock rate, and DifficultyAdjust overrides.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ModSettings {
+    bits: u32,
+    clock_rate: Option<f32>,
+    ar: Option<f32>,
+    cs: Option<f32>,
+    hp: Option<f32>,
+    od: Option<f32>,
+}
+
+impl ModSettings {
+    pub fn new(mods: &GameMods) -> Self {
+        let mut settings = Self {
+            bits: mods.bits(),
+            clock_rate: speed_change(mods),
+            ..Default::default()
+        };
+
+        for gamemod in mods.iter() {
+            match gamemod {
+                GameMod::DifficultyAdjustOsu(DifficultyAdjustOsu {
+                    circle_size,
+                    approach_rate,
+                    drain_rate,
+                    overall_difficulty,
+                    ..
+                })
+                | GameMod::DifficultyAdjustCatch(DifficultyAdjustCatch {
+                    circle_size,
+                    approach_rate,
+                    drain_rate,
+                    overall_difficulty,
+                    ..
+                }) => {
+                    settings.cs = *circle_size;
+                    settings.ar = *approach_rate;
+                    settings.hp = *drain_rate;
+                    settings.od = *overall_difficulty;
+                }
+                GameMod::DifficultyAdjustTaiko(DifficultyAdjustTaiko {
+                    drain_rate,
+                    overall_difficulty,
+                    ..
+                })
+                | GameMod::DifficultyAdjustMania(DifficultyAdjustMania {
+                    drain_rate,
+                    overall_difficulty,
+                    ..
+                }) => {
+                    settings.hp = *drain_rate;
+                    settings.od = *overall_difficulty;
+                }
+                _ => {}
+            }
+        }
+
+        settings
+    }
+
+    /// Mods without settings so only their bits matter
+    pub fn from_intermode(mods: &GameModsIntermode) -> Self {
+        Self {
+            bits: mods.bits(),
+            ..Default::default()
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// The custom clock rate, if any; otherwise it's given by the bits
+    pub fn clock_rate(&self) -> Option<f64> {
+        self.clock_rate.map(f64::from)
+    }
+
+    /// The map with its attributes replaced by the DifficultyAdjust overrides
+    pub fn adjust<'m>(&self, map: &'m Beatmap) -> Cow<'m, Beatmap> {
+        if self.ar.is_none() && self.cs.is_none() && self.hp.is_none() && self.od.is_none() {
+            return Cow::Borrowed(map);
+        }
+
+        let mut map = map.to_owned();
+
+        if let Some(ar) = self.ar {
+            map.ar = ar;
+        }
+
+        if let Some(cs) = self.cs {
+            map.cs = cs;
+        }
+
+        if let Some(hp) = self.hp {
+            map.hp = hp;
+        }
+
+        if let Some(od) = self.od {
+            map.od = od;
+        }
+
+        Cow::Owned(map)
+    }
+
+    fn floats(&self) -> [Option<u32>; 5] {
+        [self.clock_rate, self.ar, self.cs, self.hp, self.od].map(|n| n.map(f32::to_bits))
+    }
+}
+
+// Floats are compared bitwise so settings can serve as map keys
+
+impl PartialEq for ModSettings {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits && self.floats() == other.floats()
+    }
+}
+
+impl Eq for ModSettings {}
+
+impl Hash for ModSettings {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bits.hash(state);
+        self.floats().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use rosu_v2::model::mods::generated_mods::DoubleTimeOsu;
+
+    use crate::util::osu::clock_rate;
+
+    use super::*;
+
+    fn double_time(speed_change: Option<f32>) -> GameMods {
+        let dt = DoubleTimeOsu {
+            speed_change,
+            ..Default::default()
+        };
+
+        [GameMod::DoubleTimeOsu(dt)].into_iter().collect()
+    }
+
+    fn difficulty_adjust(ar: f32, od: f32) -> GameMods {
+        let da = DifficultyAdjustOsu {
+            approach_rate: Some(ar),
+            overall_difficulty: Some(od),
+            ..Default::default()
+        };
+
+        [GameMod::DifficultyAdjustOsu(da)].into_iter().collect()
+    }
+
+    fn hash(settings: &ModSettings) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        settings.hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    #[test]
+    fn custom_rates_are_different_keys() {
+        let slow = ModSettings::new(&double_time(Some(1.3)));
+        let fast = ModSettings::new(&double_time(Some(1.5)));
+
+        assert_eq!(slow.bits(), fast.bits());
+        assert_ne!(slow, fast);
+        assert_ne!(hash(&slow), hash(&fast));
+    }
+
+    #[test]
+    fn identical_settings_hash_equally() {
+        let a = ModSettings::new(&difficulty_adjust(10.0, 9.0));
+        let b = ModSettings::new(&difficulty_adjust(10.0, 9.0));
+
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn difficulty_adjust_overrides_map() {
+        let map = Beatmap {
+            ar: 9.0,
+            cs: 4.0,
+            od: 8.0,
+            ..Default::default()
+        };
+
+        let settings = ModSettings::new(&difficulty_adjust(10.0, 9.5));
+        let adjusted = settings.adjust(&map);
+
+        assert!(matches!(adjusted, Cow::Owned(_)));
+        assert_eq!((adjusted.ar, adjusted.cs, adjusted.od), (10.0, 4.0, 9.5));
+
+        let plain = ModSettings::new(&double_time(None));
+        assert!(matches!(plain.adjust(&map), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn clock_rate_prefers_custom_rate() {
+        let custom = double_time(Some(1.3));
+        assert_eq!(
+            ModSettings::new(&custom).clock_rate(),
+            Some(f64::from(1.3_f32))
+        );
+        assert_eq!(clock_rate(&custom), 1.3);
+
+        let plain = double_time(None);
+        assert_eq!(ModSettings::new(&plain).clock_rate(), None);
+        assert_eq!(clock_rate(&plain), 1.5);
+
+        let intermode = GameModsIntermode::try_from_acronyms("DT").unwrap();
+        let settings = ModSettings::from_intermode(&intermode);
+        assert_eq!(settings.bits(), intermode.bits());
+        assert_eq!(settings.clock_rate(), None);
+    }
+}
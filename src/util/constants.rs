@@ -5,15 +5,15 @@ pub const RED: u32 = 0xE74C3C;
 // Message field sizes
 pub const DESCRIPTION_SIZE: usize = 4096;
 
-// osu!
-pub const OSU_BASE: &str = "https://osu.ppy.sh/";
-pub const MAP_THUMB_URL: &str = "https://b.ppy.sh/thumb/";
-pub const AVATAR_URL: &str = "https://a.ppy.sh/";
-
 // Error messages
 pub const GENERAL_ISSUE: &str = "Something went wrong";
 pub const OSU_API_ISSUE: &str = "Some issue with the osu api";
 pub const OSU_WEB_ISSUE: &str = "Some issue with the osu website, DDoS protection?";
+pub const OSU_SESSION_EXPIRED: &str =
+    "The bot's osu! sessions expired, the bot owner needs to provide a new one";
+pub const OSU_UNREACHABLE: &str = "The osu! website is currently unreachable, try again later";
+pub const OSU_QUEUE_FULL: &str =
+    "There are too many pending osu! requests from here, try again once they're done";
 
 // Misc
 pub const BATHBOT_GITHUB: &str = "https://github.com/MaxOhn/Bathbot";
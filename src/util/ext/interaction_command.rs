@@ -128,8 +128,11 @@ impl InteractionCommandExt for InteractionCommand {
     fn error(&self, ctx: &Context, content: impl Into<String>) -> ResponseFuture<Message> {
         let embed = EmbedBuilder::new().description(content).color(RED).build();
 
+        // The error replaces the whole response, including previous content
         ctx.interaction()
             .update_response(&self.token)
+            .content(None)
+            .expect("invalid content")
             .embeds(Some(&[embed]))
             .expect("invalid embed")
             .into_future()
@@ -1,9 +1,15 @@
 use regex::Regex;
 use rosu_v2::prelude::{GameModsIntermode, UserId as OsuUserId};
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker, MessageMarker},
+    Id,
+};
 
-use super::{constants::OSU_BASE, osu::ModSelection};
+use super::osu::ModSelection;
+
+/// Links of the official osu! website, independent of the configured base url
+const OSU_SITE: &str = "https://osu.ppy.sh/";
 
-#[allow(dead_code)]
 pub fn get_osu_user_id(msg: &str) -> Option<OsuUserId> {
     OSU_URL_USER_MATCHER.captures(msg).and_then(|c| {
         c.get(1)
@@ -18,7 +24,7 @@ pub fn get_osu_map_id(msg: &str) -> Option<u32> {
         return Some(id);
     }
 
-    if !msg.contains(OSU_BASE) {
+    if !msg.contains(OSU_SITE) {
         return None;
     }
 
@@ -36,7 +42,7 @@ pub fn get_osu_mapset_id(msg: &str) -> Option<u32> {
         return Some(id);
     }
 
-    if !msg.contains(OSU_BASE) {
+    if !msg.contains(OSU_SITE) {
         return None;
     }
 
@@ -47,6 +53,34 @@ pub fn get_osu_mapset_id(msg: &str) -> Option<u32> {
         .and_then(|c| c.as_str().parse().ok())
 }
 
+/// A message of a discord message link
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DiscordMessage {
+    /// `None` for links of the form `channels/@me/...` i.e. DM messages
+    pub guild_id: Option<Id<GuildMarker>>,
+    pub channel_id: Id<ChannelMarker>,
+    pub msg_id: Id<MessageMarker>,
+}
+
+/// Guild, channel, and message id of a discord message link
+pub fn get_discord_message(msg: &str) -> Option<DiscordMessage> {
+    let captures = DISCORD_MESSAGE_MATCHER.captures(msg)?;
+
+    let guild_id = match captures.get(1)?.as_str() {
+        "@me" => None,
+        guild_id => Some(Id::new_checked(guild_id.parse().ok()?)?),
+    };
+
+    let channel_id = captures.get(2)?.as_str().parse().ok()?;
+    let msg_id = captures.get(3)?.as_str().parse().ok()?;
+
+    Some(DiscordMessage {
+        guild_id,
+        channel_id: Id::new_checked(channel_id)?,
+        msg_id: Id::new_checked(msg_id)?,
+    })
+}
+
 pub fn get_mods(msg: &str) -> Option<ModSelection> {
     let selection = if let Some(captures) = MOD_PLUS_MATCHER.captures(msg) {
         let mods = GameModsIntermode::try_from_acronyms(captures.get(1)?.as_str())?;
@@ -86,6 +120,11 @@ lazy_static::lazy_static! {
     static ref OSU_URL_MAPSET_OLD_MATCHER: Regex =
         Regex::new(r"https://osu.ppy.sh/s/(\d+)").unwrap();
 
+    static ref DISCORD_MESSAGE_MATCHER: Regex = Regex::new(
+        r"^https://(?:ptb\.|canary\.)?discord(?:app)?\.com/channels/(\d+|@me)/(\d+)/(\d+)$"
+    )
+    .unwrap();
+
     static ref MOD_PLUS_MATCHER: Regex = Regex::new(r"^\+(\w+)!?$").unwrap();
     static ref MOD_MINUS_MATCHER: Regex = Regex::new(r"^-(\w+)!$").unwrap();
 
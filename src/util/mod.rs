@@ -12,6 +12,7 @@ pub mod builder;
 pub mod constants;
 pub mod datetime;
 pub mod matcher;
+pub mod mod_equivalence;
 pub mod numbers;
 pub mod osu;
 
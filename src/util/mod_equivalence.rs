@@ -0,0 +1,179 @@
+use rosu_v2::prelude::{GameModIntermode, GameMode, GameModsIntermode};
+
+use GameModIntermode::*;
+
+/// Describes which mod combinations should be considered the same
+/// when looking at a leaderboard of the given mode
+pub struct ModEquivalence {
+    /// Groups of interchangeable mods, the first mod of a group being its canonical form
+    interchangeable: &'static [&'static [GameModIntermode]],
+    /// Mods whose presence makes no difference unless they are explicitly selected
+    optional: &'static [GameModIntermode],
+    /// Mods that are never considered, e.g. because they can't be part of the leaderboard
+    ignored: &'static [GameModIntermode],
+}
+
+const INTERCHANGEABLE: &[&[GameModIntermode]] = &[
+    &[DoubleTime, Nightcore],
+    &[HalfTime, Daycore],
+    &[SuddenDeath, Perfect],
+];
+
+static STANDARD: ModEquivalence = ModEquivalence {
+    interchangeable: INTERCHANGEABLE,
+    optional: &[],
+    ignored: &[Classic],
+};
+
+static MANIA: ModEquivalence = ModEquivalence {
+    interchangeable: INTERCHANGEABLE,
+    optional: &[Mirror],
+    // Key mods change the map so they're neither optional nor ignored
+    ignored: &[Classic],
+};
+
+impl ModEquivalence {
+    pub fn new(mode: GameMode) -> &'static Self {
+        match mode {
+            GameMode::Mania => &MANIA,
+            GameMode::Osu | GameMode::Taiko | GameMode::Catch => &STANDARD,
+        }
+    }
+
+    /// Map each mod onto its canonical form.
+    ///
+    /// Ignored mods are removed, as are optional mods unless `keep_optional` is set.
+    pub fn normalize(
+        &self,
+        mods: impl Iterator<Item = GameModIntermode>,
+        keep_optional: bool,
+    ) -> GameModsIntermode {
+        mods.filter(|gamemod| !self.ignored.contains(gamemod))
+            .filter(|gamemod| keep_optional || !self.optional.contains(gamemod))
+            .map(|gamemod| self.canonical(gamemod))
+            .collect()
+    }
+
+    /// Whether the selection contains any of the optional mods
+    pub fn selects_optional(&self, mods: &GameModsIntermode) -> bool {
+        self.optional.iter().any(|&gamemod| mods.contains(gamemod))
+    }
+
+    /// All mod combinations that are equivalent to the given ones, starting with the
+    /// canonical combination.
+    ///
+    /// For `None` i.e. the unfiltered leaderboard, the optional mods are queried on their own
+    /// so e.g. mirrored mania scores are merged into it in every scope.
+    pub fn variants(&self, mods: Option<&GameModsIntermode>) -> Vec<Option<GameModsIntermode>> {
+        let Some(mods) = mods else {
+            let optional = self
+                .optional
+                .iter()
+                .map(|&gamemod| Some(GameModsIntermode::from_iter([gamemod])));
+
+            return [None].into_iter().chain(optional).collect();
+        };
+
+        let mut variants = vec![self.normalize(mods.iter(), true)];
+
+        // Replace each mod with all of its alternatives
+        for group in self.interchangeable {
+            if !group.iter().any(|&gamemod| variants[0].contains(gamemod)) {
+                continue;
+            }
+
+            variants = variants
+                .into_iter()
+                .flat_map(|variant| {
+                    group.iter().map(move |&alternative| {
+                        variant
+                            .iter()
+                            .map(|gamemod| {
+                                if group.contains(&gamemod) {
+                                    alternative
+                                } else {
+                                    gamemod
+                                }
+                            })
+                            .collect::<GameModsIntermode>()
+                    })
+                })
+                .collect();
+        }
+
+        // Add unselected optional mods to each variant
+        for &optional in self.optional {
+            if mods.contains(optional) {
+                continue;
+            }
+
+            variants = variants
+                .into_iter()
+                .flat_map(|variant| {
+                    let with_optional = variant.iter().chain([optional]).collect();
+
+                    [variant, with_optional]
+                })
+                .collect();
+        }
+
+        variants.into_iter().map(Some).collect()
+    }
+
+    fn canonical(&self, gamemod: GameModIntermode) -> GameModIntermode {
+        self.interchangeable
+            .iter()
+            .find(|group| group.contains(&gamemod))
+            .map_or(gamemod, |group| group[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mods(acronyms: &str) -> GameModsIntermode {
+        GameModsIntermode::try_from_acronyms(acronyms).unwrap()
+    }
+
+    #[test]
+    fn variants_of_interchangeable_mods() {
+        let variants = ModEquivalence::new(GameMode::Osu).variants(Some(&mods("HDNC")));
+
+        let expected = vec![Some(mods("HDDT")), Some(mods("HDNC"))];
+
+        assert_eq!(variants, expected);
+    }
+
+    #[test]
+    fn variants_with_optional_mods() {
+        let variants = ModEquivalence::new(GameMode::Mania).variants(Some(&mods("DT4K")));
+
+        let expected = vec![
+            Some(mods("DT4K")),
+            Some(mods("DT4KMR")),
+            Some(mods("NC4K")),
+            Some(mods("NC4KMR")),
+        ];
+
+        assert_eq!(variants, expected);
+    }
+
+    #[test]
+    fn key_mods_are_kept() {
+        let variants = ModEquivalence::new(GameMode::Mania).variants(Some(&mods("4K")));
+
+        assert_eq!(variants, vec![Some(mods("4K")), Some(mods("4KMR"))]);
+    }
+
+    #[test]
+    fn variants_of_unfiltered_leaderboard() {
+        assert_eq!(
+            ModEquivalence::new(GameMode::Taiko).variants(None),
+            vec![None]
+        );
+
+        let mania = ModEquivalence::new(GameMode::Mania).variants(None);
+        assert_eq!(mania, vec![None, Some(mods("MR"))]);
+    }
+}
@@ -1,6 +1,13 @@
 use std::path::PathBuf;
 
-use rosu_v2::prelude::{GameMode, GameMods, GameModsIntermode, Grade, Score};
+use rosu_v2::{
+    model::mods::generated_mods::{
+        DaycoreCatch, DaycoreMania, DaycoreOsu, DaycoreTaiko, DoubleTimeCatch, DoubleTimeMania,
+        DoubleTimeOsu, DoubleTimeTaiko, HalfTimeCatch, HalfTimeMania, HalfTimeOsu, HalfTimeTaiko,
+        NightcoreCatch, NightcoreMania, NightcoreOsu, NightcoreTaiko,
+    },
+    prelude::{GameMod, GameModIntermode, GameMode, GameMods, GameModsIntermode, Grade, Score},
+};
 use time::OffsetDateTime;
 use tokio::{fs::File, io::AsyncWriteExt};
 use twilight_model::channel::{message::embed::Embed, Message};
@@ -8,7 +15,7 @@ use twilight_model::channel::{message::embed::Embed, Message};
 use crate::{
     core::Context,
     error::MapFileError,
-    util::{constants::OSU_BASE, matcher},
+    util::{matcher, mod_equivalence::ModEquivalence},
     CONFIG,
 };
 
@@ -19,24 +26,82 @@ pub enum ModSelection {
     Exact(GameModsIntermode),
 }
 
-#[allow(dead_code)]
-pub fn flag_url_svg(country_code: &str) -> String {
-    assert_eq!(
-        country_code.len(),
-        2,
-        "country code `{country_code}` is invalid",
-    );
+impl ModSelection {
+    /// Check whether the mods of a score satisfy the selection.
+    ///
+    /// Mods are compared according to the mode's [`ModEquivalence`] so e.g.
+    /// DT and NC are considered equivalent and CL is ignored.
+    pub fn matches(&self, mods: &GameMods, mode: GameMode) -> bool {
+        let (Self::Include(selected) | Self::Exclude(selected) | Self::Exact(selected)) = self;
+
+        let equivalence = ModEquivalence::new(mode);
+        let keep_optional = equivalence.selects_optional(selected);
+        let selected = equivalence.normalize(selected.iter(), keep_optional);
+        let mods = equivalence.normalize(mods.iter().map(GameMod::intermode), keep_optional);
+
+        match self {
+            Self::Include(_) if selected.is_empty() => mods.is_empty(),
+            Self::Include(_) => selected.iter().all(|gamemod| mods.contains(gamemod)),
+            Self::Exclude(_) if selected.is_empty() => !mods.is_empty(),
+            Self::Exclude(_) => !selected.iter().any(|gamemod| mods.contains(gamemod)),
+            Self::Exact(_) => selected == mods,
+        }
+    }
+}
 
-    const OFFSET: u32 = 0x1F1A5;
-    let bytes = country_code.as_bytes();
+/// PNG image of a country's flag since Discord can't display SVGs in embeds.
+///
+/// Returns `None` if the country code is not two ASCII letters.
+pub fn flag_url(country_code: &str) -> Option<String> {
+    let valid = country_code.len() == 2 && country_code.bytes().all(|b| b.is_ascii_alphabetic());
+
+    valid.then(|| {
+        format!(
+            "{}images/flags/{}.png",
+            CONFIG.get().unwrap().urls.osu_base,
+            country_code.to_ascii_uppercase()
+        )
+    })
+}
 
-    let url = format!(
-        "{OSU_BASE}assets/images/flags/{:x}-{:x}.svg",
-        bytes[0].to_ascii_uppercase() as u32 + OFFSET,
-        bytes[1].to_ascii_uppercase() as u32 + OFFSET
-    );
+/// Clock rate of the given mods, including a custom speed change
+pub fn clock_rate(mods: &GameMods) -> f32 {
+    if let Some(speed_change) = speed_change(mods) {
+        speed_change
+    } else if mods.contains_intermode(GameModIntermode::DoubleTime)
+        || mods.contains_intermode(GameModIntermode::Nightcore)
+    {
+        1.5
+    } else if mods.contains_intermode(GameModIntermode::HalfTime)
+        || mods.contains_intermode(GameModIntermode::Daycore)
+    {
+        0.75
+    } else {
+        1.0
+    }
+}
 
-    url
+/// Custom rate of a lazer rate-changing mod such as DT 1.3x
+pub fn speed_change(mods: &GameMods) -> Option<f32> {
+    mods.iter().find_map(|gamemod| match gamemod {
+        GameMod::HalfTimeOsu(HalfTimeOsu { speed_change, .. })
+        | GameMod::DaycoreOsu(DaycoreOsu { speed_change, .. })
+        | GameMod::DoubleTimeOsu(DoubleTimeOsu { speed_change, .. })
+        | GameMod::NightcoreOsu(NightcoreOsu { speed_change, .. })
+        | GameMod::HalfTimeTaiko(HalfTimeTaiko { speed_change, .. })
+        | GameMod::DaycoreTaiko(DaycoreTaiko { speed_change, .. })
+        | GameMod::DoubleTimeTaiko(DoubleTimeTaiko { speed_change, .. })
+        | GameMod::NightcoreTaiko(NightcoreTaiko { speed_change, .. })
+        | GameMod::HalfTimeCatch(HalfTimeCatch { speed_change, .. })
+        | GameMod::DaycoreCatch(DaycoreCatch { speed_change, .. })
+        | GameMod::DoubleTimeCatch(DoubleTimeCatch { speed_change, .. })
+        | GameMod::NightcoreCatch(NightcoreCatch { speed_change, .. })
+        | GameMod::HalfTimeMania(HalfTimeMania { speed_change, .. })
+        | GameMod::DaycoreMania(DaycoreMania { speed_change, .. })
+        | GameMod::DoubleTimeMania(DoubleTimeMania { speed_change, .. })
+        | GameMod::NightcoreMania(NightcoreMania { speed_change, .. }) => *speed_change,
+        _ => None,
+    })
 }
 
 pub fn grade_emote(grade: Grade) -> &'static str {
@@ -70,7 +135,7 @@ pub async fn prepare_beatmap_file(ctx: &Context, map_id: u32) -> Result<PathBuf,
 #[derive(Copy, Clone, Debug)]
 pub enum MapIdType {
     Map(u32),
-    Set(#[allow(unused)] u32),
+    Set(u32),
 }
 
 impl MapIdType {
@@ -289,3 +354,62 @@ macro_rules! impl_sortable_score_tuple {
 
 impl_sortable_score_tuple!((usize, Score) => 1);
 impl_sortable_score_tuple!((usize, Score, Option<f32>) => 1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(arg: &str) -> ModSelection {
+        matcher::get_mods(arg).unwrap()
+    }
+
+    fn mods(acronyms: &str, mode: GameMode) -> GameMods {
+        GameModsIntermode::try_from_acronyms(acronyms)
+            .unwrap()
+            .with_mode(mode)
+    }
+
+    fn matches(arg: &str, acronyms: &str) -> bool {
+        selection(arg).matches(&mods(acronyms, GameMode::Osu), GameMode::Osu)
+    }
+
+    #[test]
+    fn include_selection() {
+        assert!(matches("+dt", "DT"));
+        assert!(matches("+dt", "NC"));
+        assert!(matches("+dt", "HDNC"));
+        assert!(!matches("+dt", "HD"));
+        assert!(!matches("+dt", ""));
+        assert!(matches("+hddt", "HDNC"));
+        assert!(!matches("+hddt", "DT"));
+    }
+
+    #[test]
+    fn exact_selection() {
+        assert!(matches("+dt!", "NC"));
+        assert!(matches("+dt!", "DTCL"));
+        assert!(!matches("+dt!", "HDNC"));
+        assert!(matches("+hddt!", "HDNC"));
+        assert!(!matches("+hddt!", "DT"));
+    }
+
+    #[test]
+    fn exclude_selection() {
+        assert!(!matches("-hdnf!", "HD"));
+        assert!(!matches("-hdnf!", "NF"));
+        assert!(!matches("-hdnf!", "HDDT"));
+        assert!(matches("-hdnf!", "DT"));
+        assert!(matches("-hdnf!", ""));
+        assert!(!matches("-dt!", "NC"));
+    }
+
+    #[test]
+    fn optional_mods_only_count_when_selected() {
+        let dt_mirror = mods("DTMR", GameMode::Mania);
+
+        assert!(selection("+dt!").matches(&dt_mirror, GameMode::Mania));
+        assert!(selection("+mr").matches(&dt_mirror, GameMode::Mania));
+        assert!(!selection("+mr!").matches(&dt_mirror, GameMode::Mania));
+        assert!(!selection("+dt!").matches(&dt_mirror, GameMode::Osu));
+    }
+}